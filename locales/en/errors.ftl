@@ -0,0 +1,78 @@
+## Titles, by HTTP status code
+
+status-400 = Bad Request
+status-401 = Unauthorized
+status-404 = Not Found
+status-405 = Method Not Allowed
+status-406 = Not Acceptable
+status-409 = Conflict
+status-412 = Precondition Failed
+status-413 = Payload Too Large
+status-415 = Unsupported Media Type
+status-428 = Precondition Required
+status-500 = Internal Server Error
+status-503 = Service Unavailable
+status-504 = Gateway Timeout
+
+## Messages, by error code (see /error_codes)
+
+VALIDATION_FAILED = Validation error
+BAD_REQUEST = Bad request
+UNAUTHORIZED = Unauthorized
+NOT_FOUND = Not found
+TODO_NOT_FOUND = Todo not found
+METHOD_NOT_ALLOWED = Method not allowed
+CONFLICT = Conflict
+UNIQUE_VIOLATION = Unique constraint { $constraint } violated
+TODO_TITLE_TAKEN = A todo with this title already exists
+FOREIGN_KEY_VIOLATION = Foreign key constraint { $constraint } violated
+CONCURRENT_UPDATE = Conflict with a concurrent update. Please try again.
+PRECONDITION_FAILED = The resource was changed since it was read. Fetch it again and retry.
+PRECONDITION_REQUIRED = This request must be conditional. Send the ETag of the resource in If-Match.
+CHECK_VIOLATION = Check constraint { $constraint } violated
+INTERNAL_ERROR = Internal server error. Please try again later.
+SERVICE_UNAVAILABLE = Service unavailable. Please try again later.
+DATABASE_TIMEOUT = The request timed out. Please try again later.
+
+## Messages of errors built from client input, with the offending details as arguments
+
+invalid-request-body = Invalid request body: { $error }
+csv-body-without-record = Invalid request body: expected a header row and one record
+csv-body-with-several-records = Invalid request body: expected a single record
+malformed-json = Malformed JSON: { $error }
+invalid-path-parameter = Invalid path parameter: { $error }
+invalid-query-parameter = Invalid query parameter: { $error }
+invalid-header = Invalid { $header } header
+invalid-cursor = Invalid cursor: { $cursor }
+invalid-filter = Invalid filter: { $error }
+invalid-log-level = Invalid log level directive: { $error }
+items-per-page-out-of-range = items_per_page must be between 1 and { $max }
+page-out-of-range = page must be less than { $max }
+conflicting-pagination = Only one of page, after and before can be given
+cursor-with-sort-or-search = sort and q cannot be combined with after or before
+fields-with-search = fields cannot be combined with q
+empty-prefix = prefix must not be empty
+limit-out-of-range = limit must be between 1 and { $max }
+unknown-sort-column = Cannot sort by { $column }, expected one of: { $expected }
+duplicate-sort-column = Cannot sort by { $column } more than once
+unknown-field = Unknown field { $field }, expected one of: { $expected }
+no-fields = fields must name at least one field
+unsupported-content-type = Content-Type must be one of: { $expected }
+json-content-type-required = Content-Type must be application/json
+body-too-large = Request body exceeds the limit of { $limit } bytes
+body-of-length-too-large = Request body of { $length } bytes exceeds the limit of { $limit } bytes
+not-acceptable = Cannot respond with { $accept }, available types are: { $available }
+
+## Validation messages, by validator code and the parameters it carries
+
+validation-length-min-max = Must be between { $min } and { $max } characters long.
+validation-length-min = Must be at least { $min } characters long.
+validation-length-max = Must be at most { $max } characters long.
+validation-length-equal = Must be exactly { $equal } characters long.
+validation-range-min-max = Must be between { $min } and { $max }.
+validation-range-min = Must be at least { $min }.
+validation-range-max = Must be at most { $max }.
+validation-email = Must be a valid email address.
+validation-url = Must be a valid URL.
+validation-required = This field is required.
+validation-default = Invalid value.
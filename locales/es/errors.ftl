@@ -0,0 +1,78 @@
+## Títulos, por código de estado HTTP
+
+status-400 = Solicitud incorrecta
+status-401 = No autorizado
+status-404 = No encontrado
+status-405 = Método no permitido
+status-406 = No aceptable
+status-409 = Conflicto
+status-412 = Falló la condición previa
+status-413 = Contenido demasiado grande
+status-415 = Tipo de contenido no admitido
+status-428 = Se requiere una condición previa
+status-500 = Error interno del servidor
+status-503 = Servicio no disponible
+status-504 = Tiempo de espera agotado
+
+## Mensajes, por código de error (ver /error_codes)
+
+VALIDATION_FAILED = Error de validación
+BAD_REQUEST = Solicitud incorrecta
+UNAUTHORIZED = No autorizado
+NOT_FOUND = No encontrado
+TODO_NOT_FOUND = Tarea no encontrada
+METHOD_NOT_ALLOWED = Método no permitido
+CONFLICT = Conflicto
+UNIQUE_VIOLATION = Se ha violado la restricción única { $constraint }
+TODO_TITLE_TAKEN = Ya existe una tarea con este título
+FOREIGN_KEY_VIOLATION = Se ha violado la clave foránea { $constraint }
+CONCURRENT_UPDATE = Conflicto con una actualización simultánea. Inténtelo de nuevo.
+PRECONDITION_FAILED = El recurso ha cambiado desde que se leyó. Vuelva a obtenerlo e inténtelo de nuevo.
+PRECONDITION_REQUIRED = Esta solicitud debe ser condicional. Envíe la ETag del recurso en If-Match.
+CHECK_VIOLATION = Se ha violado la restricción { $constraint }
+INTERNAL_ERROR = Error interno del servidor. Inténtelo de nuevo más tarde.
+SERVICE_UNAVAILABLE = Servicio no disponible. Inténtelo de nuevo más tarde.
+DATABASE_TIMEOUT = La solicitud ha tardado demasiado. Inténtelo de nuevo más tarde.
+
+## Mensajes de errores causados por la solicitud, con los detalles como argumentos
+
+invalid-request-body = Cuerpo de la solicitud no válido: { $error }
+csv-body-without-record = Cuerpo de la solicitud no válido: se esperaba una fila de encabezado y un registro
+csv-body-with-several-records = Cuerpo de la solicitud no válido: se esperaba un único registro
+malformed-json = JSON mal formado: { $error }
+invalid-path-parameter = Parámetro de ruta no válido: { $error }
+invalid-query-parameter = Parámetro de consulta no válido: { $error }
+invalid-header = Cabecera { $header } no válida
+invalid-cursor = Cursor no válido: { $cursor }
+invalid-filter = Filtro no válido: { $error }
+invalid-log-level = Directiva de nivel de registro no válida: { $error }
+items-per-page-out-of-range = items_per_page debe estar entre 1 y { $max }
+page-out-of-range = page debe ser menor que { $max }
+conflicting-pagination = Solo se puede indicar uno de page, after y before
+cursor-with-sort-or-search = sort y q no se pueden combinar con after ni before
+fields-with-search = fields no se puede combinar con q
+empty-prefix = prefix no puede estar vacío
+limit-out-of-range = limit debe estar entre 1 y { $max }
+unknown-sort-column = No se puede ordenar por { $column }, se esperaba uno de: { $expected }
+duplicate-sort-column = No se puede ordenar por { $column } más de una vez
+unknown-field = Campo desconocido { $field }, se esperaba uno de: { $expected }
+no-fields = fields debe indicar al menos un campo
+unsupported-content-type = Content-Type debe ser uno de: { $expected }
+json-content-type-required = Content-Type debe ser application/json
+body-too-large = El cuerpo de la solicitud supera el límite de { $limit } bytes
+body-of-length-too-large = El cuerpo de la solicitud de { $length } bytes supera el límite de { $limit } bytes
+not-acceptable = No se puede responder con { $accept }, los tipos disponibles son: { $available }
+
+## Mensajes de validación, por código del validador y los parámetros que incluye
+
+validation-length-min-max = Debe tener entre { $min } y { $max } caracteres.
+validation-length-min = Debe tener al menos { $min } caracteres.
+validation-length-max = Debe tener como máximo { $max } caracteres.
+validation-length-equal = Debe tener exactamente { $equal } caracteres.
+validation-range-min-max = Debe estar entre { $min } y { $max }.
+validation-range-min = Debe ser como mínimo { $min }.
+validation-range-max = Debe ser como máximo { $max }.
+validation-email = Debe ser una dirección de correo válida.
+validation-url = Debe ser una URL válida.
+validation-required = Este campo es obligatorio.
+validation-default = Valor no válido.
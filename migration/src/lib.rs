@@ -2,6 +2,9 @@ pub use sea_orm_migration::prelude::*;
 
 //❗ After creating a new migration file, remove the sample migration below 👇
 mod m20220101_000001_create_todo_table;
+mod m20261018_000001_add_todo_search;
+mod m20261018_000002_add_todo_title_trigram_index;
+mod m20261018_000003_set_todo_updated_at;
 
 pub struct Migrator;
 
@@ -10,7 +13,10 @@ impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
         vec![
             //❗ After creating a new migration file, remove the sample migration below 👇
-            Box::new(m20220101_000001_create_todo_table::Migration)
+            Box::new(m20220101_000001_create_todo_table::Migration),
+            Box::new(m20261018_000001_add_todo_search::Migration),
+            Box::new(m20261018_000002_add_todo_title_trigram_index::Migration),
+            Box::new(m20261018_000003_set_todo_updated_at::Migration),
         ]
     }
 }
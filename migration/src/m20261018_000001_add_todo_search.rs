@@ -0,0 +1,34 @@
+use crate::sea_orm::{Statement, ConnectionTrait};
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// Adds a full-text search vector over the title and description, kept up to date by Postgres, and a GIN index
+/// to search it. Title matches weigh more than description matches when ranking.
+/// ❗ The text search configuration must match `SEARCH_CONFIG` in `modules::todo::service`.
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let statements = [
+            r#"
+            ALTER TABLE todo ADD COLUMN search tsvector GENERATED ALWAYS AS (
+                setweight(to_tsvector('english', title), 'A') ||
+                setweight(to_tsvector('english', description), 'B')
+            ) STORED
+            "#,
+            "CREATE INDEX todo_search_idx ON todo USING GIN (search)",
+        ];
+        for sql in statements {
+            let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+            manager.get_connection().execute(stmt).await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let sql = "ALTER TABLE todo DROP COLUMN search";
+        let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+        manager.get_connection().execute(stmt).await.map(|_| ())
+    }
+}
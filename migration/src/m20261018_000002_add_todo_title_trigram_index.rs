@@ -0,0 +1,29 @@
+use crate::sea_orm::{Statement, ConnectionTrait};
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// Adds a trigram index on the title, which serves both `ILIKE 'prefix%'` and similarity searches
+/// for title suggestions.
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let statements = [
+            "CREATE EXTENSION IF NOT EXISTS pg_trgm",
+            "CREATE INDEX todo_title_trgm_idx ON todo USING GIN (title gin_trgm_ops)",
+        ];
+        for sql in statements {
+            let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+            manager.get_connection().execute(stmt).await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // The extension is left installed: other schemas may depend on it.
+        let sql = "DROP INDEX todo_title_trgm_idx";
+        let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+        manager.get_connection().execute(stmt).await.map(|_| ())
+    }
+}
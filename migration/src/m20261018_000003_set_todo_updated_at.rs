@@ -0,0 +1,39 @@
+use crate::sea_orm::{Statement, ConnectionTrait};
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// Bumps `updated_at` on every update of a todo, so it can serve as the version of the row in ETags.
+/// `clock_timestamp()` rather than `NOW()`, which would repeat for several updates in one transaction.
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let statements = [
+            r#"CREATE FUNCTION todo_set_updated_at() RETURNS trigger AS $$
+            BEGIN
+                NEW.updated_at := clock_timestamp();
+                RETURN NEW;
+            END;
+            $$ LANGUAGE plpgsql"#,
+            "CREATE TRIGGER todo_set_updated_at BEFORE UPDATE ON todo FOR EACH ROW EXECUTE FUNCTION todo_set_updated_at()",
+        ];
+        for sql in statements {
+            let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+            manager.get_connection().execute(stmt).await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let statements = [
+            "DROP TRIGGER todo_set_updated_at ON todo",
+            "DROP FUNCTION todo_set_updated_at()",
+        ];
+        for sql in statements {
+            let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+            manager.get_connection().execute(stmt).await?;
+        }
+        Ok(())
+    }
+}
@@ -2,7 +2,7 @@ use secrecy::{ExposeSecret, Secret};
 use serde::Deserialize;
 use std::env;
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Clone)]
 #[serde(rename_all = "snake_case")]
 pub enum SslMode {
     Disable,
@@ -13,13 +13,42 @@ pub enum SslMode {
     VerifyFull
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorFormat {
+    Default,
+    Problem,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorReportingSettings {
+    Disabled,
+    Sentry { dsn: Secret<String> },
+    File { path: String },
+}
+
+#[derive(Deserialize, Clone)]
 pub struct Settings {
     pub database: DatabaseSettings,
     pub application_port: u16,
+    /// Token required to call the admin endpoints. The admin endpoints are disabled if unset.
+    pub admin_token: Option<Secret<String>>,
+    /// Whether to add a `Server-Timing` header to every response.
+    pub server_timing: bool,
+    /// How error responses are rendered unless the client asks for `application/problem+json`.
+    pub error_format: ErrorFormat,
+    /// Where 5xx errors and panics are reported.
+    pub error_reporting: ErrorReportingSettings,
+    /// Whether error reports include the address of the client that made the request.
+    pub error_reporting_send_ip: bool,
+    /// The largest page size list endpoints accept.
+    pub max_items_per_page: usize,
+    /// Whether updates and deletes must send the ETag of the resource in `If-Match`.
+    pub require_if_match: bool,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Clone)]
 pub struct DatabaseSettings {
     pub username: String,
     pub password: Secret<String>,
@@ -27,6 +56,7 @@ pub struct DatabaseSettings {
     pub host: String,
     pub database_name: String,
     pub ssl_mode: SslMode,
+    pub slow_query_threshold_ms: u64,
 }
 
 impl DatabaseSettings {
@@ -76,8 +106,68 @@ pub fn get_config() -> Result<Settings, dotenv::Error> {
         "" => SslMode::Prefer,
         other => panic!("SSL_MODE: {} is not a valid value", other),
     };
+    // if left empty, default to 500
+    let db_slow_query_threshold_ms = env::var("DATABASE_SLOW_QUERY_THRESHOLD_MS")
+        .ok()
+        .filter(|threshold| !threshold.is_empty())
+        .unwrap_or("500".to_string());
+    let db_slow_query_threshold_ms = db_slow_query_threshold_ms
+        .parse()
+        .expect("DATABASE_SLOW_QUERY_THRESHOLD_MS is not a number");
     let app_port = env::var("APPLICATION_PORT").expect("APPLICATION_PORT is not set in .env file");
     let app_port = app_port.parse().expect("APPLICATION_PORT is not a number");
+    let admin_token = env::var("ADMIN_TOKEN")
+        .ok()
+        .filter(|token| !token.is_empty())
+        .map(Secret::new);
+    let server_timing = env::var("SERVER_TIMING").unwrap_or("".to_string());
+    let server_timing = match server_timing.as_str() {
+        "true" => true,
+        // if left empty, default to false
+        "false" | "" => false,
+        other => panic!("SERVER_TIMING: {} is not a valid value", other),
+    };
+    let error_format = env::var("ERROR_FORMAT").unwrap_or("".to_string());
+    let error_format = match error_format.as_str() {
+        "problem" => ErrorFormat::Problem,
+        // if left empty, default to the default format
+        "default" | "" => ErrorFormat::Default,
+        other => panic!("ERROR_FORMAT: {} is not a valid value", other),
+    };
+    let error_reporting = env::var("ERROR_REPORTING").unwrap_or("".to_string());
+    let error_reporting = match error_reporting.as_str() {
+        "sentry" => ErrorReportingSettings::Sentry {
+            dsn: Secret::new(env::var("ERROR_REPORTING_DSN").expect("ERROR_REPORTING_DSN is not set in .env file")),
+        },
+        "file" => ErrorReportingSettings::File {
+            path: env::var("ERROR_REPORTING_PATH").expect("ERROR_REPORTING_PATH is not set in .env file"),
+        },
+        // if left empty, default to disabled
+        "disabled" | "" => ErrorReportingSettings::Disabled,
+        other => panic!("ERROR_REPORTING: {} is not a valid value", other),
+    };
+    let error_reporting_send_ip = env::var("ERROR_REPORTING_SEND_IP").unwrap_or("".to_string());
+    let error_reporting_send_ip = match error_reporting_send_ip.as_str() {
+        "true" => true,
+        // if left empty, default to false
+        "false" | "" => false,
+        other => panic!("ERROR_REPORTING_SEND_IP: {} is not a valid value", other),
+    };
+    // if left empty, default to 100
+    let max_items_per_page = env::var("MAX_ITEMS_PER_PAGE")
+        .ok()
+        .filter(|max| !max.is_empty())
+        .unwrap_or("100".to_string());
+    let max_items_per_page = max_items_per_page
+        .parse()
+        .expect("MAX_ITEMS_PER_PAGE is not a number");
+    let require_if_match = env::var("REQUIRE_IF_MATCH").unwrap_or("".to_string());
+    let require_if_match = match require_if_match.as_str() {
+        "true" => true,
+        // if left empty, default to false
+        "false" | "" => false,
+        other => panic!("REQUIRE_IF_MATCH: {} is not a valid value", other),
+    };
     Ok(Settings {
         database: DatabaseSettings {
             username: db_username,
@@ -85,8 +175,16 @@ pub fn get_config() -> Result<Settings, dotenv::Error> {
             port: db_port,
             host: db_host,
             database_name: db_name,
-            ssl_mode: db_ssl_mode
+            ssl_mode: db_ssl_mode,
+            slow_query_threshold_ms: db_slow_query_threshold_ms,
         },
         application_port: app_port,
+        admin_token,
+        server_timing,
+        error_format,
+        error_reporting,
+        error_reporting_send_ip,
+        max_items_per_page,
+        require_if_match,
     })
 }
\ No newline at end of file
@@ -0,0 +1,160 @@
+use crate::core::middleware::server_timing::record_db_time;
+use sea_orm::{ConnectionTrait, DbBackend, DbConn, DbErr, ExecResult, QueryResult, Statement};
+use std::future::Future;
+use std::time::{Duration, Instant};
+use tracing::Instrument;
+
+/// The threshold used when none is configured, as `DATABASE_SLOW_QUERY_THRESHOLD_MS` defaults to it.
+const DEFAULT_SLOW_QUERY_THRESHOLD: Duration = Duration::from_millis(500);
+
+/// The connection pool, registered as app data, along with the threshold above which its queries are logged at WARN.
+pub struct Database {
+    pub conn: DbConn,
+    pub slow_query_threshold: Duration,
+}
+
+impl Database {
+    pub fn new(conn: DbConn, slow_query_threshold: Duration) -> Self {
+        Self { conn, slow_query_threshold }
+    }
+
+    /// Wraps the pool to instrument its statements.
+    pub fn instrumented(&self) -> InstrumentedConnection<'_, DbConn> {
+        InstrumentedConnection::new(&self.conn, self.slow_query_threshold)
+    }
+}
+
+impl From<DbConn> for Database {
+    fn from(conn: DbConn) -> Self {
+        Self::new(conn, DEFAULT_SLOW_QUERY_THRESHOLD)
+    }
+}
+
+/// Collapses whitespace and replaces numbered placeholders with `?`, so that the same query always logs the same way.
+/// Parameter values are never part of the statement text, only their placeholders.
+pub fn normalize_sql(sql: &str) -> String {
+    let collapsed = sql.split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut normalized = String::with_capacity(collapsed.len());
+    let mut chars = collapsed.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '$' && chars.peek().is_some_and(|next| next.is_ascii_digit()) {
+            while chars.peek().is_some_and(|next| next.is_ascii_digit()) {
+                chars.next();
+            }
+            normalized.push('?');
+        } else {
+            normalized.push(c);
+        }
+    }
+    normalized
+}
+
+/// Wraps a connection (or transaction) so that every statement runs inside a span carrying its normalized SQL,
+/// the number of rows returned or affected, and its duration.
+pub struct InstrumentedConnection<'a, C: ConnectionTrait> {
+    inner: &'a C,
+    slow_query_threshold: Duration,
+}
+
+impl<'a, C: ConnectionTrait> InstrumentedConnection<'a, C> {
+    pub fn new(inner: &'a C, slow_query_threshold: Duration) -> Self {
+        Self { inner, slow_query_threshold }
+    }
+}
+
+async fn instrument<T, F, Fut>(
+    stmt: Statement,
+    slow_query_threshold: Duration,
+    run: F,
+    rows: fn(&T) -> u64,
+) -> Result<T, DbErr>
+where
+    F: FnOnce(Statement) -> Fut,
+    Fut: Future<Output = Result<T, DbErr>>,
+{
+    let sql = normalize_sql(&stmt.sql);
+    let span = tracing::info_span!(
+        "Database query",
+        db.statement = %sql,
+        db.rows = tracing::field::Empty,
+        db.elapsed_ms = tracing::field::Empty,
+    );
+
+    let start = Instant::now();
+    let result = run(stmt).instrument(span.clone()).await;
+    let elapsed = start.elapsed();
+    record_db_time(elapsed);
+
+    span.record("db.elapsed_ms", elapsed.as_millis() as u64);
+    if let Ok(value) = &result {
+        span.record("db.rows", rows(value));
+    }
+    if elapsed >= slow_query_threshold {
+        tracing::warn!(
+            parent: &span,
+            db.statement = %sql,
+            db.elapsed_ms = elapsed.as_millis() as u64,
+            "Slow query"
+        );
+    }
+
+    result
+}
+
+#[async_trait::async_trait]
+impl<'a, C: ConnectionTrait> ConnectionTrait for InstrumentedConnection<'a, C> {
+    fn get_database_backend(&self) -> DbBackend {
+        self.inner.get_database_backend()
+    }
+
+    async fn execute(&self, stmt: Statement) -> Result<ExecResult, DbErr> {
+        instrument(
+            stmt,
+            self.slow_query_threshold,
+            |stmt| self.inner.execute(stmt),
+            |result: &ExecResult| result.rows_affected(),
+        )
+        .await
+    }
+
+    async fn query_one(&self, stmt: Statement) -> Result<Option<QueryResult>, DbErr> {
+        instrument(
+            stmt,
+            self.slow_query_threshold,
+            |stmt| self.inner.query_one(stmt),
+            |row: &Option<QueryResult>| row.is_some() as u64,
+        )
+        .await
+    }
+
+    async fn query_all(&self, stmt: Statement) -> Result<Vec<QueryResult>, DbErr> {
+        instrument(
+            stmt,
+            self.slow_query_threshold,
+            |stmt| self.inner.query_all(stmt),
+            |rows: &Vec<QueryResult>| rows.len() as u64,
+        )
+        .await
+    }
+
+    fn support_returning(&self) -> bool {
+        self.inner.support_returning()
+    }
+
+    fn is_mock_connection(&self) -> bool {
+        self.inner.is_mock_connection()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_sql() {
+        assert_eq!(
+            normalize_sql("SELECT \"todo\".\"id\"\n    FROM \"todo\" WHERE \"todo\".\"id\" = $1 LIMIT $12"),
+            r#"SELECT "todo"."id" FROM "todo" WHERE "todo"."id" = ? LIMIT ?"#
+        );
+    }
+}
@@ -0,0 +1,418 @@
+use actix_web::{http::header, HttpMessage, HttpRequest};
+use chrono::{SecondsFormat, Utc};
+use secrecy::{ExposeSecret, Secret};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tracing_actix_web::RequestId;
+use uuid::Uuid;
+
+use crate::core::config::{ErrorReportingSettings, Settings};
+use crate::shared::errors::CustomError;
+
+/// Request headers attached to reports. Anything else, credentials and cookies in particular, is left out.
+const REPORTED_HEADERS: &[header::HeaderName] = &[
+    header::ACCEPT,
+    header::ACCEPT_LANGUAGE,
+    header::CONTENT_LENGTH,
+    header::CONTENT_TYPE,
+    header::HOST,
+    header::REFERER,
+    header::USER_AGENT,
+];
+
+/// One exception in a chain, as Sentry expects it.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ExceptionValue {
+    #[serde(rename = "type")]
+    pub exception_type: String,
+    pub value: String,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ExceptionList {
+    pub values: Vec<ExceptionValue>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Default)]
+pub struct RequestContext {
+    pub method: String,
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query_string: Option<String>,
+    pub headers: BTreeMap<String, String>,
+}
+
+/// The client that made the request. The API has no user accounts, so the client is identified by address only,
+/// and only if `ERROR_REPORTING_SEND_IP` is set.
+#[derive(Serialize, Debug, Clone, PartialEq, Default)]
+pub struct UserContext {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ip_address: Option<String>,
+}
+
+/// What an event records about a request, copied out of it so that reporting never holds on to the request.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RequestDetails {
+    context: RequestContext,
+    user: UserContext,
+    request_id: Option<String>,
+}
+
+impl RequestDetails {
+    /// Copies the request's method, URL, safe headers, client address and request id.
+    pub fn new(req: &HttpRequest) -> Self {
+        let connection_info = req.connection_info();
+        Self {
+            context: RequestContext {
+                method: req.method().to_string(),
+                url: format!("{}://{}{}", connection_info.scheme(), connection_info.host(), req.path()),
+                query_string: Some(req.query_string().to_owned()).filter(|query| !query.is_empty()),
+                headers: REPORTED_HEADERS
+                    .iter()
+                    .filter_map(|name| {
+                        let value = req.headers().get(name)?.to_str().ok()?;
+                        Some((name.to_string(), value.to_owned()))
+                    })
+                    .collect(),
+            },
+            user: UserContext {
+                ip_address: connection_info.realip_remote_addr().map(|addr| addr.to_owned()),
+            },
+            request_id: req.extensions().get::<RequestId>().map(|request_id| request_id.to_string()),
+        }
+    }
+}
+
+/// A captured server error or panic, serialized as a Sentry event.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ErrorEvent {
+    pub event_id: String,
+    pub timestamp: String,
+    pub level: String,
+    pub platform: String,
+    pub release: String,
+    pub message: String,
+    pub exception: ExceptionList,
+    pub request: RequestContext,
+    pub user: UserContext,
+    pub tags: BTreeMap<String, String>,
+    pub extra: BTreeMap<String, Value>,
+}
+
+/// Returns the name of an error's type or variant from its `Debug` output, such as `Query` for `DbErr::Query(..)`.
+fn error_type(e: &dyn std::fmt::Debug) -> String {
+    let debug = format!("{:?}", e);
+    debug
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .next()
+        .filter(|name| !name.is_empty())
+        .unwrap_or("Error")
+        .to_owned()
+}
+
+impl ErrorEvent {
+    fn new(level: &str, message: String, exception: Vec<ExceptionValue>) -> Self {
+        Self {
+            event_id: Uuid::new_v4().simple().to_string(),
+            timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            level: level.to_owned(),
+            platform: "rust".to_owned(),
+            release: env!("CARGO_PKG_VERSION").to_owned(),
+            message,
+            exception: ExceptionList { values: exception },
+            request: RequestContext::default(),
+            user: UserContext::default(),
+            tags: BTreeMap::new(),
+            extra: BTreeMap::new(),
+        }
+    }
+
+    /// Builds an event from an error and its chain of sources. Sentry lists chained exceptions oldest first,
+    /// so the root cause comes first and the error itself last. The backtrace of a `CustomError`, if one was
+    /// captured, goes in `extra`, as for panics.
+    pub fn from_error(e: &(dyn std::error::Error + 'static)) -> Self {
+        let mut values = vec![];
+        let mut current: Option<&(dyn std::error::Error + 'static)> = Some(e);
+        while let Some(error) = current {
+            values.push(ExceptionValue {
+                exception_type: error_type(&error),
+                value: error.to_string(),
+            });
+            current = error.source();
+        }
+        values.reverse();
+        let mut event = Self::new("error", e.to_string(), values);
+        if let Some(backtrace) = e.downcast_ref::<CustomError>().and_then(CustomError::backtrace) {
+            event.extra.insert("backtrace".to_owned(), Value::String(backtrace.to_string()));
+        }
+        event
+    }
+
+    /// Builds an event from an error known only by its message.
+    pub fn from_message(exception_type: &str, message: String) -> Self {
+        let exception = vec![ExceptionValue {
+            exception_type: exception_type.to_owned(),
+            value: message.clone(),
+        }];
+        Self::new("error", message, exception)
+    }
+
+    /// Builds an event from a caught panic.
+    pub fn from_panic(message: String, backtrace: String) -> Self {
+        let mut event = Self::from_message("panic", message);
+        event.level = "fatal".to_owned();
+        event.extra.insert("backtrace".to_owned(), Value::String(backtrace));
+        event
+    }
+
+    /// Attaches the request's method, URL, safe headers, client address and request id.
+    pub fn with_request(self, req: &HttpRequest) -> Self {
+        self.with_request_details(RequestDetails::new(req))
+    }
+
+    /// Attaches request details copied out earlier, for when the request itself is gone.
+    pub fn with_request_details(mut self, details: RequestDetails) -> Self {
+        self.request = details.context;
+        self.user = details.user;
+        if let Some(request_id) = details.request_id {
+            self.tags.insert("request_id".to_owned(), request_id);
+        }
+        self
+    }
+
+    pub fn with_tag(mut self, name: &str, value: impl ToString) -> Self {
+        self.tags.insert(name.to_owned(), value.to_string());
+        self
+    }
+
+    /// Serializes this event as a Sentry envelope: an envelope header, an item header and the event, one per line.
+    pub fn to_envelope(&self, dsn: Option<&str>) -> String {
+        let payload = serde_json::to_string(self).unwrap_or_default();
+        let mut envelope_header = json!({
+            "event_id": self.event_id,
+            "sent_at": Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+        });
+        if let Some(dsn) = dsn {
+            envelope_header["dsn"] = Value::String(dsn.to_owned());
+        }
+        let item_header = json!({ "type": "event", "length": payload.len() });
+        format!("{}\n{}\n{}\n", envelope_header, item_header, payload)
+    }
+}
+
+/// A destination for error events.
+pub trait ErrorSink: Send + Sync {
+    fn capture(&self, event: ErrorEvent);
+}
+
+/// Sends events to a Sentry (or Sentry-compatible) server, given its DSN.
+pub struct SentrySink {
+    dsn: Secret<String>,
+    endpoint: String,
+    auth: String,
+    client: reqwest::Client,
+}
+
+impl SentrySink {
+    /// Parses a DSN of the form `https://<public_key>@<host>/<project_id>`.
+    pub fn new(dsn: Secret<String>) -> Result<Self, String> {
+        let url = reqwest::Url::parse(dsn.expose_secret()).map_err(|e| e.to_string())?;
+        let public_key = url.username();
+        if public_key.is_empty() {
+            return Err("the DSN has no public key".to_owned());
+        }
+        let project_id = url
+            .path_segments()
+            .and_then(|mut segments| segments.next_back())
+            .filter(|project_id| !project_id.is_empty())
+            .ok_or("the DSN has no project id")?;
+        let host = url.host_str().ok_or("the DSN has no host")?;
+        let port = url.port().map(|port| format!(":{}", port)).unwrap_or_default();
+        let endpoint = format!("{}://{}{}/api/{}/envelope/", url.scheme(), host, port, project_id);
+        let auth = format!(
+            "Sentry sentry_version=7, sentry_client={}/{}, sentry_key={}",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION"),
+            public_key
+        );
+        Ok(Self {
+            dsn,
+            endpoint,
+            auth,
+            client: reqwest::Client::new(),
+        })
+    }
+}
+
+impl ErrorSink for SentrySink {
+    fn capture(&self, event: ErrorEvent) {
+        let envelope = event.to_envelope(Some(self.dsn.expose_secret()));
+        let request = self
+            .client
+            .post(&self.endpoint)
+            .header("X-Sentry-Auth", &self.auth)
+            .header(header::CONTENT_TYPE.as_str(), "application/x-sentry-envelope")
+            .body(envelope);
+        actix_web::rt::spawn(async move {
+            match request.send().await.and_then(|res| res.error_for_status()) {
+                Ok(_) => {}
+                Err(e) => tracing::warn!(error.message = %e, "Failed to send error report"),
+            }
+        });
+    }
+}
+
+/// Appends envelopes to a local file, one after the other. The file is written on a thread of its own, so that
+/// reporting never blocks a worker.
+pub struct FileSink {
+    sender: Mutex<Sender<String>>,
+}
+
+impl FileSink {
+    pub fn new(path: String) -> Self {
+        let (sender, receiver) = mpsc::channel::<String>();
+        thread::spawn(move || {
+            for envelope in receiver {
+                let written = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&path)
+                    .and_then(|mut file| file.write_all(envelope.as_bytes()));
+                if let Err(e) = written {
+                    tracing::warn!(error.message = %e, path = %path, "Failed to write error report");
+                }
+            }
+        });
+        Self { sender: Mutex::new(sender) }
+    }
+}
+
+impl ErrorSink for FileSink {
+    fn capture(&self, event: ErrorEvent) {
+        let sender = self.sender.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if sender.send(event.to_envelope(None)).is_err() {
+            tracing::warn!("Failed to write error report: the writer thread has stopped");
+        }
+    }
+}
+
+/// Keeps events in memory, for tests.
+#[derive(Clone, Default)]
+pub struct MemorySink {
+    events: Arc<Mutex<Vec<ErrorEvent>>>,
+}
+
+impl MemorySink {
+    pub fn events(&self) -> Vec<ErrorEvent> {
+        self.events.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+    }
+}
+
+impl ErrorSink for MemorySink {
+    fn capture(&self, event: ErrorEvent) {
+        self.events.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).push(event);
+    }
+}
+
+/// Reports server errors and panics to the configured sink. Registered as app data and used by the middleware.
+#[derive(Clone)]
+pub struct ErrorReporter {
+    sink: Option<Arc<dyn ErrorSink>>,
+    send_ip_address: bool,
+}
+
+impl ErrorReporter {
+    /// A reporter sending events to `sink`, without the client's address.
+    pub fn new(sink: impl ErrorSink + 'static) -> Self {
+        Self { sink: Some(Arc::new(sink)), send_ip_address: false }
+    }
+
+    /// A reporter that drops every event.
+    pub fn disabled() -> Self {
+        Self { sink: None, send_ip_address: false }
+    }
+
+    /// Whether events keep the address of the client that made the request.
+    pub fn send_ip_address(mut self, send_ip_address: bool) -> Self {
+        self.send_ip_address = send_ip_address;
+        self
+    }
+
+    pub fn from_settings(settings: &Settings) -> Self {
+        let reporter = match &settings.error_reporting {
+            ErrorReportingSettings::Disabled => Self::disabled(),
+            ErrorReportingSettings::Sentry { dsn } => {
+                let sink = SentrySink::new(dsn.clone())
+                    .unwrap_or_else(|e| panic!("ERROR_REPORTING_DSN is not a valid DSN: {}", e));
+                Self::new(sink)
+            }
+            ErrorReportingSettings::File { path } => Self::new(FileSink::new(path.clone())),
+        };
+        reporter.send_ip_address(settings.error_reporting_send_ip)
+    }
+
+    pub fn capture(&self, mut event: ErrorEvent) {
+        if !self.send_ip_address {
+            event.user.ip_address = None;
+        }
+        if let Some(sink) = &self.sink {
+            tracing::info!(error_report.event_id = %event.event_id, "Reporting error");
+            sink.capture(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    #[test]
+    fn test_to_envelope() {
+        let event = ErrorEvent::from_panic("boom".to_owned(), "at src/main.rs:1:1".to_owned());
+        let envelope = event.to_envelope(Some("https://key@sentry.example.com/42"));
+        let lines: Vec<&str> = envelope.lines().collect();
+        assert_eq!(lines.len(), 3);
+
+        let envelope_header: Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(envelope_header["event_id"], event.event_id.as_str());
+        assert_eq!(envelope_header["dsn"], "https://key@sentry.example.com/42");
+        let item_header: Value = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(item_header["type"], "event");
+        assert_eq!(item_header["length"], lines[2].len());
+        let payload: Value = serde_json::from_str(lines[2]).unwrap();
+        assert_eq!(payload["level"], "fatal");
+        assert_eq!(payload["exception"]["values"][0]["type"], "panic");
+        assert_eq!(payload["extra"]["backtrace"], "at src/main.rs:1:1");
+    }
+
+    #[test]
+    fn test_ip_address_is_only_sent_when_enabled() {
+        let req = TestRequest::default().peer_addr("203.0.113.7:443".parse().unwrap()).to_http_request();
+        let event = || ErrorEvent::from_message("Error", "boom".to_owned()).with_request(&req);
+
+        let sink = MemorySink::default();
+        ErrorReporter::new(sink.clone()).capture(event());
+        assert_eq!(sink.events()[0].user.ip_address, None);
+
+        let sink = MemorySink::default();
+        ErrorReporter::new(sink.clone()).send_ip_address(true).capture(event());
+        assert_eq!(sink.events()[0].user.ip_address.as_deref(), Some("203.0.113.7"));
+    }
+
+    #[test]
+    fn test_sentry_sink_parses_dsn() {
+        let sink = SentrySink::new(Secret::new("https://abc123@sentry.example.com:9000/42".to_owned())).unwrap();
+        assert_eq!(sink.endpoint, "https://sentry.example.com:9000/api/42/envelope/");
+        assert!(sink.auth.contains("sentry_key=abc123"));
+
+        assert!(SentrySink::new(Secret::new("https://sentry.example.com/42".to_owned())).is_err());
+        assert!(SentrySink::new(Secret::new("https://abc123@sentry.example.com/".to_owned())).is_err());
+    }
+}
@@ -0,0 +1,189 @@
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::{web, Error};
+use futures_util::future::LocalBoxFuture;
+use futures_util::FutureExt;
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::cell::RefCell;
+use std::future::{ready, Ready};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Once;
+
+use crate::core::error_reporting::{ErrorEvent, ErrorReporter, RequestDetails};
+use crate::shared::errors::CustomError;
+
+thread_local! {
+    /// The location and backtrace of the last panic on this thread, captured by the panic hook.
+    static LAST_PANIC: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+static INSTALL_PANIC_HOOK: Once = Once::new();
+
+/// Installs a panic hook that records where a panic happened, since the backtrace is gone by the time it is caught.
+fn install_panic_hook() {
+    INSTALL_PANIC_HOOK.call_once(|| {
+        let previous_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let location = info
+                .location()
+                .map(|location| location.to_string())
+                .unwrap_or_default();
+            let details = format!("at {}\n{}", location, Backtrace::capture());
+            LAST_PANIC.with(|last_panic| *last_panic.borrow_mut() = Some(details));
+            previous_hook(info);
+        }));
+    });
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Box<dyn Any>".to_owned()
+    }
+}
+
+/// Turns a panic while handling a request into a logged, well-formed 500 response instead of a dropped connection.
+/// Panics are also reported to the `ErrorReporter` registered as app data, if any.
+/// The request is gone by the time a panic is caught, so the 500 is returned as an error for actix to render, in the
+/// default locale.
+pub struct CatchPanic;
+
+impl CatchPanic {
+    pub fn new() -> Self {
+        install_panic_hook();
+        Self
+    }
+}
+
+impl Default for CatchPanic {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for CatchPanic
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = CatchPanicMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(CatchPanicMiddleware { service }))
+    }
+}
+
+pub struct CatchPanicMiddleware<S> {
+    service: S,
+}
+
+/// Logs and reports a caught panic, and returns the error answering the request.
+fn panic_error(
+    reporter: Option<web::Data<ErrorReporter>>,
+    request: RequestDetails,
+    payload: Box<dyn Any + Send>,
+) -> Error {
+    let details = LAST_PANIC.with(|last_panic| last_panic.borrow_mut().take()).unwrap_or_default();
+    let message = panic_message(&*payload);
+    tracing::error!(
+        panic.message = %message,
+        panic.backtrace = %details,
+        "Request handler panicked"
+    );
+    if let Some(reporter) = reporter {
+        reporter.capture(ErrorEvent::from_panic(message, details).with_request_details(request));
+    }
+    CustomError::ServerError.into()
+}
+
+impl<S, B> Service<ServiceRequest> for CatchPanicMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        // ❗ Copy what the report needs instead of cloning the request: the router needs it to be the only handle.
+        let (http_req, _) = req.parts_mut();
+        let reporter = http_req.app_data::<web::Data<ErrorReporter>>().cloned();
+        let request = RequestDetails::new(http_req);
+        let fut = match panic::catch_unwind(AssertUnwindSafe(|| self.service.call(req))) {
+            Ok(fut) => fut,
+            Err(payload) => {
+                let e = panic_error(reporter, request, payload);
+                return Box::pin(async move { Err(e) });
+            }
+        };
+
+        Box::pin(async move {
+            match AssertUnwindSafe(fut).catch_unwind().await {
+                Ok(res) => res,
+                Err(payload) => Err(panic_error(reporter, request, payload)),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::error_reporting::MemorySink;
+    use actix_web::{dev::Service, test, App, HttpResponse};
+
+    async fn panics() -> HttpResponse {
+        panic!("boom")
+    }
+
+    #[actix_web::test]
+    async fn test_requests_without_panics_are_routed() {
+        let app = test::init_service(
+            App::new()
+                .wrap(CatchPanic::new())
+                .route("/todos/{id}", web::get().to(|id: web::Path<i32>| async move { id.to_string() })),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/todos/7").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status().as_u16(), 200);
+        assert_eq!(test::read_body(res).await, "7");
+    }
+
+    #[actix_web::test]
+    async fn test_panic_returns_500() {
+        let sink = MemorySink::default();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(ErrorReporter::new(sink.clone())))
+                .wrap(CatchPanic::new())
+                .route("/panic", web::get().to(panics)),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/panic").to_request();
+        let e = app.call(req).await.expect_err("the panic was not turned into an error");
+        let res = e.error_response();
+        assert_eq!(res.status().as_u16(), 500);
+        let body: serde_json::Value =
+            serde_json::from_slice(&actix_web::body::to_bytes(res.into_body()).await.unwrap()).unwrap();
+        assert_eq!(body["status_code"], 500);
+
+        let events = sink.events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].level, "fatal");
+        assert_eq!(events[0].message, "boom");
+        assert_eq!(events[0].request.url, "http://localhost:8080/panic");
+    }
+}
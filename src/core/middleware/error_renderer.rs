@@ -0,0 +1,182 @@
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{self, HeaderValue};
+use actix_web::{Error, HttpMessage, HttpRequest, ResponseError};
+use futures_util::future::LocalBoxFuture;
+use std::future::{ready, Ready};
+use tracing_actix_web::RequestId;
+
+use crate::core::config::ErrorFormat;
+use crate::shared::errors::{CustomError, ProblemDetails, PROBLEM_JSON};
+use crate::shared::i18n::{localizer, DEFAULT_LOCALE};
+use crate::shared::responses::json_response;
+
+/// Re-renders `CustomError` responses with request-specific context.
+/// Errors are rendered as RFC 7807 `application/problem+json` if configured, or if the client asks for it in its `Accept` header,
+/// and their messages are localized according to the `Accept-Language` header.
+pub struct ErrorRenderer {
+    format: ErrorFormat,
+}
+
+impl ErrorRenderer {
+    pub fn new(format: ErrorFormat) -> Self {
+        Self { format }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for ErrorRenderer
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = ErrorRendererMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ErrorRendererMiddleware {
+            service,
+            format: self.format.clone(),
+        }))
+    }
+}
+
+pub struct ErrorRendererMiddleware<S> {
+    service: S,
+    format: ErrorFormat,
+}
+
+fn accepts_problem_json(req: &HttpRequest) -> bool {
+    req.headers()
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|accept| accept.contains(PROBLEM_JSON))
+}
+
+impl<S, B> Service<ServiceRequest> for ErrorRendererMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let format = self.format.clone();
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let res = fut.await?;
+            let wants_problem = matches!(format, ErrorFormat::Problem) || accepts_problem_json(res.request());
+            let locale = localizer().negotiate(
+                res.request()
+                    .headers()
+                    .get(header::ACCEPT_LANGUAGE)
+                    .and_then(|value| value.to_str().ok()),
+            );
+            let rendered = res
+                .response()
+                .error()
+                .and_then(|e| e.as_error::<CustomError>())
+                .and_then(|e| {
+                    if wants_problem {
+                        let problem = ProblemDetails {
+                            instance: Some(res.request().path().to_owned()),
+                            request_id: res.request().extensions().get::<RequestId>().map(|id| id.to_string()),
+                            ..e.problem_details(locale)
+                        };
+                        let mut response = json_response(e.status_code(), &problem);
+                        response
+                            .headers_mut()
+                            .insert(header::CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON));
+                        Some(response)
+                    } else if locale != DEFAULT_LOCALE {
+                        Some(e.render(locale))
+                    } else {
+                        None
+                    }
+                });
+            let mut response = match rendered {
+                Some(response) => response,
+                None => return Ok(res.map_into_left_body()),
+            };
+
+            let (req, original) = res.into_parts();
+            let rendered_headers: Vec<_> = response.headers().keys().cloned().collect();
+            for (name, value) in original.headers() {
+                if !rendered_headers.contains(name) && name != header::CONTENT_LENGTH {
+                    response.headers_mut().append(name.clone(), value.clone());
+                }
+            }
+            if let Ok(language) = HeaderValue::from_str(locale) {
+                response.headers_mut().insert(header::CONTENT_LANGUAGE, language);
+            }
+            Ok(ServiceResponse::new(req, response).map_into_right_body())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, web, App};
+
+    async fn not_found() -> Result<String, CustomError> {
+        Err(CustomError::NotFound)
+    }
+
+    #[actix_web::test]
+    async fn test_renders_problem_json_when_accepted() {
+        let app = test::init_service(
+            App::new()
+                .wrap(ErrorRenderer::new(ErrorFormat::Default))
+                .route("/todos/1", web::get().to(not_found)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/todos/1")
+            .insert_header((header::ACCEPT, PROBLEM_JSON))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status().as_u16(), 404);
+        assert_eq!(res.headers().get(header::CONTENT_TYPE).unwrap(), PROBLEM_JSON);
+        let body: serde_json::Value = test::read_body_json(res).await;
+        assert_eq!(body["type"], "/error_codes#NOT_FOUND");
+        assert_eq!(body["code"], "NOT_FOUND");
+        assert_eq!(body["title"], "Not Found");
+        assert_eq!(body["status"], 404);
+        assert_eq!(body["instance"], "/todos/1");
+
+        let req = test::TestRequest::get().uri("/todos/1").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.headers().get(header::CONTENT_TYPE).unwrap(), "application/json");
+    }
+
+    #[actix_web::test]
+    async fn test_localizes_messages_from_accept_language() {
+        let app = test::init_service(
+            App::new()
+                .wrap(ErrorRenderer::new(ErrorFormat::Default))
+                .route("/todos/1", web::get().to(not_found)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/todos/1")
+            .insert_header((header::ACCEPT_LANGUAGE, "es-ES,es;q=0.9,en;q=0.8"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status().as_u16(), 404);
+        assert_eq!(res.headers().get(header::CONTENT_LANGUAGE).unwrap(), "es");
+        let body: serde_json::Value = test::read_body_json(res).await;
+        assert_eq!(body["error"], "No encontrado");
+        assert_eq!(body["message"], "No encontrado");
+        assert_eq!(body["code"], "NOT_FOUND");
+    }
+}
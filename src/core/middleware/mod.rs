@@ -0,0 +1,4 @@
+pub mod catch_panic;
+pub mod error_renderer;
+pub mod report_errors;
+pub mod server_timing;
\ No newline at end of file
@@ -0,0 +1,148 @@
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::{web, Error};
+use futures_util::future::LocalBoxFuture;
+use std::future::{ready, Ready};
+
+use crate::core::error_reporting::{ErrorEvent, ErrorReporter};
+use crate::shared::errors::{error_chain, CustomError};
+
+/// Logs every 5xx response along with its chain of sources, once, and reports it to the `ErrorReporter`
+/// registered as app data. Database errors answered with a 4xx, such as constraint violations, are logged at WARN
+/// and not reported. Panics are logged and reported by `CatchPanic`.
+pub struct ReportErrors;
+
+impl<S, B> Transform<S, ServiceRequest> for ReportErrors
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = ReportErrorsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ReportErrorsMiddleware { service }))
+    }
+}
+
+pub struct ReportErrorsMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for ReportErrorsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let res = fut.await?;
+            let error = match res.response().error() {
+                Some(error) => error,
+                None => return Ok(res),
+            };
+            let custom_error = error.as_error::<CustomError>();
+            let chain = match custom_error {
+                Some(e) => error_chain(e),
+                None => error.to_string(),
+            };
+            if !res.status().is_server_error() {
+                // Database errors the client caused, such as constraint violations, are still worth a record
+                if custom_error.is_some_and(CustomError::is_database_error) {
+                    tracing::warn!(
+                        error.message = %error,
+                        error.chain = %chain,
+                        http.status_code = res.status().as_u16(),
+                        "Request failed"
+                    );
+                }
+                return Ok(res);
+            }
+            tracing::error!(
+                error.message = %error,
+                error.chain = %chain,
+                http.status_code = res.status().as_u16(),
+                "Request failed"
+            );
+
+            let reporter = match res.request().app_data::<web::Data<ErrorReporter>>() {
+                Some(reporter) => reporter,
+                None => return Ok(res),
+            };
+
+            let event = match custom_error {
+                Some(e) => ErrorEvent::from_error(e).with_tag("error.code", e.code().as_str()),
+                None => ErrorEvent::from_message("Error", error.to_string()),
+            };
+            reporter.capture(
+                event
+                    .with_request(res.request())
+                    .with_tag("status_code", res.status().as_u16()),
+            );
+            Ok(res)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::error_reporting::MemorySink;
+    use crate::shared::errors::ErrorSource;
+    use actix_web::{test, App, HttpResponse};
+
+    async fn server_error() -> Result<HttpResponse, CustomError> {
+        Err(CustomError::Internal {
+            source: ErrorSource::from_message("connection reset"),
+        })
+    }
+
+    async fn not_found() -> Result<HttpResponse, CustomError> {
+        Err(CustomError::NotFound)
+    }
+
+    #[actix_web::test]
+    async fn test_reports_server_errors_only() {
+        let sink = MemorySink::default();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(ErrorReporter::new(sink.clone())))
+                .wrap(ReportErrors)
+                .route("/error", web::get().to(server_error))
+                .route("/missing", web::get().to(not_found)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/error?page=1")
+            .insert_header(("authorization", "Bearer secret"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status().as_u16(), 500);
+        let req = test::TestRequest::get().uri("/missing").to_request();
+        test::call_service(&app, req).await;
+
+        let events = sink.events();
+        assert_eq!(events.len(), 1);
+        let event = &events[0];
+        assert_eq!(event.level, "error");
+        assert_eq!(event.exception.values.len(), 2);
+        assert_eq!(event.exception.values[0].value, "connection reset");
+        assert_eq!(event.exception.values[1].exception_type, "Internal");
+        assert_eq!(event.request.method, "GET");
+        assert_eq!(event.request.query_string.as_deref(), Some("page=1"));
+        assert!(!event.request.headers.contains_key("authorization"));
+        assert_eq!(event.tags["status_code"], "500");
+        assert_eq!(event.tags["error.code"], "INTERNAL_ERROR");
+    }
+}
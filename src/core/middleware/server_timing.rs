@@ -0,0 +1,112 @@
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::Error;
+use futures_util::future::LocalBoxFuture;
+use std::cell::Cell;
+use std::future::{ready, Ready};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+tokio::task_local! {
+    static TIMINGS: Rc<Timings>;
+}
+
+/// Time spent in each phase of the current request.
+#[derive(Default)]
+struct Timings {
+    db: Cell<Duration>,
+    serialization: Cell<Duration>,
+}
+
+impl Timings {
+    fn header_value(&self, total: Duration) -> String {
+        format!(
+            "total;desc=\"Total\";dur={:.3}, db;desc=\"Database\";dur={:.3}, ser;desc=\"Serialization\";dur={:.3}",
+            as_millis(total),
+            as_millis(self.db.get()),
+            as_millis(self.serialization.get()),
+        )
+    }
+}
+
+fn as_millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Adds time spent on a database query to the current request, if it is being timed.
+pub fn record_db_time(elapsed: Duration) {
+    let _ = TIMINGS.try_with(|timings| timings.db.set(timings.db.get() + elapsed));
+}
+
+/// Adds time spent serializing a response body to the current request, if it is being timed.
+pub fn record_serialization_time(elapsed: Duration) {
+    let _ = TIMINGS.try_with(|timings| timings.serialization.set(timings.serialization.get() + elapsed));
+}
+
+/// Adds a `Server-Timing` header with the total, database and serialization time of each request.
+pub struct ServerTiming;
+
+impl<S, B> Transform<S, ServiceRequest> for ServerTiming
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = ServerTimingMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ServerTimingMiddleware { service }))
+    }
+}
+
+pub struct ServerTimingMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for ServerTimingMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let timings = Rc::new(Timings::default());
+        let start = Instant::now();
+        let fut = TIMINGS.scope(timings.clone(), self.service.call(req));
+
+        Box::pin(async move {
+            let mut res = fut.await?;
+            if let Ok(value) = HeaderValue::from_str(&timings.header_value(start.elapsed())) {
+                res.headers_mut().insert(HeaderName::from_static("server-timing"), value);
+            }
+            Ok(res)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_value() {
+        let timings = Timings::default();
+        // outside of a timed request, recording is a no-op
+        record_db_time(Duration::from_millis(1));
+        timings.db.set(Duration::from_millis(4));
+        timings.serialization.set(Duration::from_micros(250));
+
+        assert_eq!(
+            timings.header_value(Duration::from_millis(12)),
+            r#"total;desc="Total";dur=12.000, db;desc="Database";dur=4.000, ser;desc="Serialization";dur=0.250"#
+        );
+    }
+}
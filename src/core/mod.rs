@@ -1,3 +1,6 @@
 pub mod config;
+pub mod database;
+pub mod error_reporting;
+pub mod middleware;
 pub mod startup;
 pub mod telemetry;
\ No newline at end of file
@@ -1,29 +1,132 @@
-use actix_web::{web, App, HttpServer};
+use actix_web::{http::Method, middleware::Condition, web, App, HttpResponse, HttpServer, Resource, Route};
 use actix_web::dev::Server;
 use std::net::TcpListener;
+use std::time::Duration;
 use sea_orm::DbConn;
 use tracing_actix_web::TracingLogger;
+use crate::core::config::Settings;
+use crate::core::database::Database;
+use crate::core::error_reporting::ErrorReporter;
+use crate::core::middleware::catch_panic::CatchPanic;
+use crate::core::middleware::error_renderer::ErrorRenderer;
+use crate::core::middleware::report_errors::ReportErrors;
+use crate::core::middleware::server_timing::ServerTiming;
+use crate::core::telemetry::LogLevelHandle;
+use crate::modules::admin::controller::{get_log_level, put_log_level};
+use crate::modules::error_codes::controller::get_error_codes;
 use crate::modules::health_check::controller::health_check;
-use crate::modules::todo::controller::{create_todo, update_todo, get_list_of_todos, get_todo, bulk_delete_todos, delete_todo};
+use crate::shared::errors::CustomError;
+use crate::modules::todo::controller::{create_todo, update_todo, get_list_of_todos, get_todo, suggest_todos, bulk_delete_todos, delete_todo};
+
+/// Builds a resource that answers each method of `routes` with its handler, and any other method with 405 and an
+/// `Allow` header listing the methods of `routes`.
+fn resource(path: &str, routes: Vec<(Method, Route)>) -> Resource {
+    let allowed: Vec<String> = routes.iter().map(|(method, _)| method.to_string()).collect();
+    let mut resource = web::resource(path);
+    for (method, route) in routes {
+        resource = resource.route(route.method(method));
+    }
+    resource.default_service(web::to(move || {
+        let allowed = allowed.clone();
+        async move { Err::<HttpResponse, _>(CustomError::MethodNotAllowed { allowed }) }
+    }))
+}
+
+/// Answers requests to paths that match no resource.
+async fn fallback() -> Result<HttpResponse, CustomError> {
+    Err(CustomError::NotFound)
+}
+
+/// Registers every controller.
+/// ❗ `/todos/suggest` has to come before `/todos/{id}`, which would otherwise match it.
+fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(resource("/health_check", vec![(Method::GET, web::to(health_check))]))
+        .service(resource(
+            "/todos",
+            vec![
+                (Method::GET, web::to(get_list_of_todos)),
+                (Method::POST, web::to(create_todo)),
+                (Method::DELETE, web::to(bulk_delete_todos)),
+            ],
+        ))
+        .service(resource("/todos/suggest", vec![(Method::GET, web::to(suggest_todos))]))
+        .service(resource(
+            "/todos/{id}",
+            vec![
+                (Method::GET, web::to(get_todo)),
+                (Method::PUT, web::to(update_todo)),
+                (Method::DELETE, web::to(delete_todo)),
+            ],
+        ))
+        .service(resource(
+            "/admin/log_level",
+            vec![(Method::GET, web::to(get_log_level)), (Method::PUT, web::to(put_log_level))],
+        ))
+        .service(resource("/error_codes", vec![(Method::GET, web::to(get_error_codes))]));
+}
 
 /// Runs the HTTP server.
-pub fn run(listener: TcpListener, db: DbConn) -> Result<Server, std::io::Error> {
-    let db = web::Data::new(db);
+pub fn run(
+    listener: TcpListener,
+    db: DbConn,
+    config: Settings,
+    log_level: LogLevelHandle,
+) -> Result<Server, std::io::Error> {
+    let slow_query_threshold = Duration::from_millis(config.database.slow_query_threshold_ms);
+    let db = web::Data::new(Database::new(db, slow_query_threshold));
+    let server_timing = config.server_timing;
+    let error_format = config.error_format.clone();
+    let error_reporter = web::Data::new(ErrorReporter::from_settings(&config));
+    let config = web::Data::new(config);
+    let log_level = web::Data::new(log_level);
     let server = HttpServer::new(move || {
         App::new()
+            .wrap(ReportErrors)
+            .wrap(CatchPanic::new())
+            .wrap(ErrorRenderer::new(error_format.clone()))
+            .wrap(Condition::new(server_timing, ServerTiming))
             .wrap(TracingLogger::default())
-            // Register your controllers below 👇
-            .service(health_check)
-            .service(get_list_of_todos)
-            .service(get_todo)
-            .service(create_todo)
-            .service(update_todo)
-            .service(delete_todo)
-            .service(bulk_delete_todos)
+            // Register your controllers in `routes` 👆
+            .configure(routes)
+            .default_service(web::route().to(fallback))
             // Register application-wide shared data below 👇
             .app_data(db.clone()) // 👈 ❗Important: Register the database connection pool
+            .app_data(config.clone())
+            .app_data(log_level.clone())
+            .app_data(error_reporter.clone())
+            // Render extractor failures in the same shape as every other error
+            .app_data(web::JsonConfig::default().error_handler(|e, _| CustomError::from(e).into()))
+            .app_data(web::PathConfig::default().error_handler(|e, _| CustomError::from(e).into()))
+            .app_data(web::QueryConfig::default().error_handler(|e, _| CustomError::from(e).into()))
     })
         .listen(listener)?
         .run();
     Ok(server)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::header, test};
+
+    #[actix_web::test]
+    async fn test_allow_lists_the_methods_of_the_resource() {
+        let app = test::init_service(
+            App::new()
+                .service(resource(
+                    "/todos/{id}",
+                    vec![(Method::GET, web::to(HttpResponse::Ok)), (Method::PUT, web::to(HttpResponse::Ok))],
+                ))
+                .default_service(web::route().to(fallback)),
+        )
+        .await;
+
+        let res = test::call_service(&app, test::TestRequest::get().uri("/todos/1").to_request()).await;
+        assert_eq!(res.status().as_u16(), 200);
+        let res = test::call_service(&app, test::TestRequest::delete().uri("/todos/1").to_request()).await;
+        assert_eq!(res.status().as_u16(), 405);
+        assert_eq!(res.headers().get(header::ALLOW).unwrap(), "GET, PUT");
+        let res = test::call_service(&app, test::TestRequest::get().uri("/unknown").to_request()).await;
+        assert_eq!(res.status().as_u16(), 404);
+    }
+}
@@ -14,26 +14,28 @@ async fn main() -> std::io::Result<()> {
     let application_telemetry_path = env::var("APPLICATION_TELEMETRY_PATH").unwrap_or_else(|_| "".to_string());
     let migration = env::var("MIGRATION").unwrap_or_else(|_| "".to_string());
 
-    match application_telemetry_path {
+    let log_level = match application_telemetry_path {
         application_telemetry_path if application_telemetry_path != "" => {
             // Set up a subscriber for logging to files, rolling daily
-            let subscriber = get_subscriber(
+            let (subscriber, log_level) = get_subscriber(
                 APPLICATION_NAME.to_owned(),
                 "info".to_string(),
                 tracing_appender::rolling::daily(application_telemetry_path, "log"),
             );
             init_subscriber(subscriber);
+            log_level
         }
         _ => {
             // Set up a subscriber for logging to the terminal -- good for development
-            let subscriber = get_subscriber(
+            let (subscriber, log_level) = get_subscriber(
                 APPLICATION_NAME.to_owned(),
                 "info".to_string(),
                 std::io::stdout,
             );
             init_subscriber(subscriber);
+            log_level
         }
-    }
+    };
 
     // Read the configuration from the environment.
     let config = get_config().expect("Failed to read configuration.");
@@ -55,5 +57,5 @@ async fn main() -> std::io::Result<()> {
     let listener = TcpListener::bind(address)?;
 
     // Run the App 🚀
-    run(listener, conn)?.await
+    run(listener, conn, config, log_level)?.await
 }
@@ -0,0 +1,66 @@
+use crate::{
+    core::{config::Settings, telemetry::LogLevelHandle},
+    modules::admin::{
+        dtos::update_log_level_dto::UpdateLogLevelDto,
+        service::{authorize, find_log_level, update_log_level},
+    },
+    shared::{errors::CustomError, requests::Body, responses::negotiated_response},
+};
+use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse};
+use serde::Serialize;
+use std::time::Duration;
+use validator::Validate;
+
+#[derive(Serialize)]
+struct LogLevelResponse {
+    directive: String,
+    default_directive: String,
+    ttl_seconds: Option<u64>,
+}
+
+impl LogLevelResponse {
+    /// Describes the directive in effect, with the seconds left until it reverts, rounded up.
+    fn new(directive: String, log_level: &LogLevelHandle) -> Self {
+        Self {
+            directive,
+            default_directive: log_level.default_directive().to_owned(),
+            ttl_seconds: log_level
+                .remaining_ttl()
+                .map(|ttl| ttl.as_secs() + u64::from(ttl.subsec_nanos() > 0)),
+        }
+    }
+}
+
+#[tracing::instrument(name = "Get log level", skip_all)]
+pub async fn get_log_level(
+    req: HttpRequest,
+    config: web::Data<Settings>,
+    log_level: web::Data<LogLevelHandle>,
+) -> Result<HttpResponse, CustomError> {
+    authorize(&req, config.admin_token.as_ref())?;
+    let directive = find_log_level(&log_level)?;
+    Ok(negotiated_response(&req, StatusCode::OK, &LogLevelResponse::new(directive, &log_level)))
+}
+
+#[tracing::instrument(
+    name = "Put log level",
+    skip_all,
+    fields(directive = %update_log_level_dto.directive, ttl_seconds = ?update_log_level_dto.ttl_seconds)
+)]
+pub async fn put_log_level(
+    req: HttpRequest,
+    config: web::Data<Settings>,
+    log_level: web::Data<LogLevelHandle>,
+    update_log_level_dto: Body<UpdateLogLevelDto>,
+) -> Result<HttpResponse, CustomError> {
+    authorize(&req, config.admin_token.as_ref())?;
+    if let Err(e) = update_log_level_dto.validate() {
+        return Err(CustomError::ValidationError { e });
+    }
+    let directive = update_log_level(
+        &log_level,
+        update_log_level_dto.directive.as_str(),
+        update_log_level_dto.ttl_seconds.map(Duration::from_secs),
+    )?;
+    Ok(negotiated_response(&req, StatusCode::OK, &LogLevelResponse::new(directive, &log_level)))
+}
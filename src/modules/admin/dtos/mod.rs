@@ -0,0 +1 @@
+pub mod update_log_level_dto;
@@ -0,0 +1,9 @@
+use validator::Validate;
+
+#[derive(serde::Deserialize, Validate)]
+pub struct UpdateLogLevelDto {
+    #[validate(length(min = 1, max = 1000))]
+    pub directive: String,
+    #[validate(range(min = 1, max = 86400))]
+    pub ttl_seconds: Option<u64>,
+}
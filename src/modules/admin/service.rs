@@ -0,0 +1,98 @@
+use actix_web::{http::header, HttpRequest};
+use secrecy::{ExposeSecret, Secret};
+use std::time::Duration;
+
+use crate::core::telemetry::LogLevelHandle;
+use crate::shared::errors::{CustomError, ErrorSource};
+use crate::shared::i18n::Message;
+
+/// Checks the request's bearer token against the configured admin token.
+#[tracing::instrument(name = "Authorize admin", skip_all)]
+pub fn authorize(req: &HttpRequest, admin_token: Option<&Secret<String>>) -> Result<(), CustomError> {
+    let admin_token = admin_token.ok_or(CustomError::Unauthorized)?;
+    let provided = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or(CustomError::Unauthorized)?;
+
+    if !constant_time_eq(provided.as_bytes(), admin_token.expose_secret().as_bytes()) {
+        return Err(CustomError::Unauthorized);
+    }
+
+    Ok(())
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[tracing::instrument(name = "Find log level", skip_all)]
+pub fn find_log_level(log_level: &LogLevelHandle) -> Result<String, CustomError> {
+    log_level.directive().map_err(|e| CustomError::Internal {
+        source: ErrorSource::from_message(format!("Failed to read the log level: {}", e)),
+    })
+}
+
+/// Changes the log level, reverting to the default directive after `ttl` if given.
+#[tracing::instrument(name = "Update log level", skip(log_level))]
+pub fn update_log_level(
+    log_level: &LogLevelHandle,
+    directive: &str,
+    ttl: Option<Duration>,
+) -> Result<String, CustomError> {
+    let generation = log_level.set_directive(directive, ttl).map_err(|e| {
+        tracing::info!(error.message = %e, "Invalid log level directive");
+        CustomError::BadRequestWithMsg {
+            message: Message::new("invalid-log-level").arg("error", e),
+        }
+    })?;
+
+    if let Some(ttl) = ttl {
+        let log_level = log_level.clone();
+        actix_web::rt::spawn(async move {
+            actix_web::rt::time::sleep(ttl).await;
+            match log_level.reset_if_unchanged(generation) {
+                Ok(true) => tracing::info!("Reverted the log level to the default directive"),
+                Ok(false) => {}
+                Err(e) => tracing::error!(error.message = %e, "Failed to revert the log level"),
+            }
+        });
+    }
+
+    find_log_level(log_level)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    #[test]
+    fn test_authorize() {
+        let token = Secret::new("s3cret".to_string());
+
+        let req = TestRequest::default()
+            .insert_header((header::AUTHORIZATION, "Bearer s3cret"))
+            .to_http_request();
+        assert_eq!(authorize(&req, Some(&token)), Ok(()));
+
+        let req = TestRequest::default()
+            .insert_header((header::AUTHORIZATION, "Bearer wrong"))
+            .to_http_request();
+        assert_eq!(authorize(&req, Some(&token)), Err(CustomError::Unauthorized));
+
+        let req = TestRequest::default().to_http_request();
+        assert_eq!(authorize(&req, Some(&token)), Err(CustomError::Unauthorized));
+
+        // admin endpoints are disabled without a configured token
+        let req = TestRequest::default()
+            .insert_header((header::AUTHORIZATION, "Bearer s3cret"))
+            .to_http_request();
+        assert_eq!(authorize(&req, None), Err(CustomError::Unauthorized));
+    }
+}
@@ -0,0 +1,21 @@
+use crate::shared::{error_codes::ErrorCode, responses::negotiated_list_response};
+use actix_web::{http::StatusCode, HttpRequest, HttpResponse};
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct ErrorCodeResponse {
+    code: ErrorCode,
+    description: &'static str,
+}
+
+#[tracing::instrument(name = "Get error codes", skip_all)]
+pub async fn get_error_codes(req: HttpRequest) -> HttpResponse {
+    let codes: Vec<ErrorCodeResponse> = ErrorCode::ALL
+        .iter()
+        .map(|code| ErrorCodeResponse {
+            code: *code,
+            description: code.description(),
+        })
+        .collect();
+    negotiated_list_response(&req, StatusCode::OK, &codes, &["code", "description"], &codes)
+}
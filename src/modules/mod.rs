@@ -1,2 +1,4 @@
+pub mod admin;
+pub mod error_codes;
 pub mod health_check;
 pub mod todo;
\ No newline at end of file
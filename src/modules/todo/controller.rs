@@ -1,89 +1,282 @@
 use crate::{
+    core::{config::Settings, database::Database},
     modules::todo::{
         dtos::{create_todo_dto::CreateTodoDto, update_todo_dto::UpdateTodoDto},
-        service::{find_todo_by_id, find_todos, insert_todo, update_todo_by_id, delete_todo_by_id, bulk_delete_todos_by_ids},
+        service::{
+            find_todo_by_id, find_todo_fields_by_id, find_todos, find_todos_by_cursor, insert_todo,
+            update_todo_by_id, delete_todo_by_id, bulk_delete_todos_by_ids, parse_fields, parse_sort, search_todos,
+            suggest_titles, CursorDirection, TodoFields, TodoFilter, DEFAULT_ITEMS_PER_PAGE, FILTER_FIELDS,
+        },
+    },
+    shared::{
+        cursor::Cursor,
+        errors::CustomError,
+        etag::{etag, if_match, insert_etag, insert_write_etag, is_not_modified, not_modified},
+        filter::parse_filter,
+        i18n::Message,
+        requests::Body,
+        responses::{negotiated_list_response, negotiated_response, write_response, CustomResponse, Format},
     },
-    shared::{errors::CustomError, responses::CustomResponse},
 };
-use actix_web::{delete, get, post, put, web, HttpResponse};
-use sea_orm::DbConn;
+use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse, Responder};
+use entity::todo;
+use sea_orm::prelude::DateTimeWithTimeZone;
 use serde::Deserialize;
+use serde::Serialize;
 use validator::Validate;
 
+/// How many suggestions `GET /todos/suggest` returns by default, and at most.
+const DEFAULT_SUGGESTIONS: usize = 5;
+const MAX_SUGGESTIONS: usize = 20;
+
+/// The largest row offset a page may start at.
+const MAX_OFFSET: usize = i64::MAX as usize;
+
 #[derive(Deserialize)]
 pub struct GetTodosQuery {
+    q: Option<String>,
     query_string: Option<String>,
+    description: Option<String>,
+    done: Option<bool>,
+    created_from: Option<DateTimeWithTimeZone>,
+    created_to: Option<DateTimeWithTimeZone>,
+    updated_from: Option<DateTimeWithTimeZone>,
+    updated_to: Option<DateTimeWithTimeZone>,
+    filter: Option<String>,
+    sort: Option<String>,
+    fields: Option<String>,
     page: Option<usize>,
     items_per_page: Option<usize>,
+    after: Option<String>,
+    before: Option<String>,
 }
 
-#[get("/todos")]
+impl GetTodosQuery {
+    fn todo_filter(&self) -> Result<TodoFilter, CustomError> {
+        Ok(TodoFilter {
+            query_string: self.query_string.clone(),
+            description: self.description.clone(),
+            done: self.done,
+            created_from: self.created_from,
+            created_to: self.created_to,
+            updated_from: self.updated_from,
+            updated_to: self.updated_to,
+            expression: match &self.filter {
+                Some(filter) => Some(parse_filter(filter, FILTER_FIELDS)?),
+                None => None,
+            },
+        })
+    }
+}
+
+/// `q` searches the words of the title and description and ranks the hits, see `search_todos`.
+/// Pages by offset with `page`, or by keyset with the opaque `after` and `before` cursors.
+/// `filter` takes an expression such as `done eq false and title contains 'pay'`, see `shared::filter`.
+/// `sort` takes a list of columns such as `done,-updated_at` and only applies to offset pages.
+/// `fields` takes a list of columns such as `id,title,done` and only selects those.
+#[tracing::instrument(
+    name = "Get list of todos",
+    skip_all,
+    fields(q = ?query.q, query_string = ?query.query_string, sort = ?query.sort, fields = ?query.fields, page = ?query.page, items_per_page = ?query.items_per_page)
+)]
 pub async fn get_list_of_todos(
-    conn: web::Data<DbConn>,
+    req: HttpRequest,
+    conn: web::Data<Database>,
+    config: web::Data<Settings>,
     query: web::Query<GetTodosQuery>,
 ) -> Result<HttpResponse, CustomError> {
-    let results = find_todos(
-        &conn,
-        query.query_string.clone(),
-        query.items_per_page,
-        query.page,
-    )
-    .await?;
-    Ok(HttpResponse::Ok().json(results))
+    if let Some(items_per_page) = query.items_per_page {
+        if items_per_page == 0 || items_per_page > config.max_items_per_page {
+            return Err(CustomError::BadRequestWithMsg {
+                message: Message::new("items-per-page-out-of-range").arg("max", config.max_items_per_page),
+            });
+        }
+    }
+    if let Some(page) = query.page {
+        // The offset of the page must fit the `BIGINT` Postgres takes.
+        let max_page = MAX_OFFSET / query.items_per_page.unwrap_or(DEFAULT_ITEMS_PER_PAGE);
+        if page >= max_page {
+            return Err(CustomError::BadRequestWithMsg {
+                message: Message::new("page-out-of-range").arg("max", max_page),
+            });
+        }
+    }
+    let direction = match (&query.after, &query.before, query.page) {
+        (None, None, _) => None,
+        (Some(after), None, None) => Some(CursorDirection::After(Cursor::decode(after)?)),
+        (None, Some(before), None) => Some(CursorDirection::Before(Cursor::decode(before)?)),
+        _ => {
+            return Err(CustomError::BadRequestWithMsg {
+                message: Message::new("conflicting-pagination"),
+            })
+        }
+    };
+    let filter = query.todo_filter()?;
+    let sort = parse_sort(query.sort.as_deref().unwrap_or_default())?;
+    let fields = query.fields.as_deref().map(parse_fields).transpose()?;
+    let q = query.q.as_deref().map(str::trim).filter(|q| !q.is_empty());
+    if let Some(direction) = direction {
+        if !sort.is_empty() || q.is_some() {
+            return Err(CustomError::BadRequestWithMsg {
+                message: Message::new("cursor-with-sort-or-search"),
+            });
+        }
+        let res = match fields {
+            Some(fields) => find_todos_by_cursor::<TodoFields>(&conn, &filter, &fields, query.items_per_page, direction)
+                .await?
+                .respond_to(&req),
+            None => find_todos_by_cursor::<todo::Model>(&conn, &filter, &[], query.items_per_page, direction)
+                .await?
+                .respond_to(&req),
+        };
+        return Ok(res);
+    }
+    if let Some(q) = q {
+        if fields.is_some() {
+            return Err(CustomError::BadRequestWithMsg {
+                message: Message::new("fields-with-search"),
+            });
+        }
+        let results = search_todos(&conn, q, &filter, &sort, query.items_per_page, query.page).await?;
+        return Ok(results.respond_to(&req));
+    }
+    let res = match fields {
+        Some(fields) => find_todos::<TodoFields>(&conn, &filter, &sort, &fields, query.items_per_page, query.page)
+            .await?
+            .respond_to(&req),
+        None => find_todos::<todo::Model>(&conn, &filter, &sort, &[], query.items_per_page, query.page)
+            .await?
+            .respond_to(&req),
+    };
+    Ok(res)
+}
+
+#[derive(Serialize)]
+struct SuggestionsResponse<T> {
+    items: Vec<T>,
 }
 
-#[get("/todos/{id}")]
+#[derive(Deserialize)]
+pub struct SuggestTodosQuery {
+    prefix: String,
+    limit: Option<usize>,
+}
+
+/// ❗ `/todos/suggest` is registered before `/todos/{id}`, which would otherwise match it.
+#[tracing::instrument(name = "Suggest todos", skip_all, fields(limit = ?query.limit))]
+pub async fn suggest_todos(
+    req: HttpRequest,
+    conn: web::Data<Database>,
+    query: web::Query<SuggestTodosQuery>,
+) -> Result<HttpResponse, CustomError> {
+    let prefix = query.prefix.trim();
+    if prefix.is_empty() {
+        return Err(CustomError::BadRequestWithMsg {
+            message: Message::new("empty-prefix"),
+        });
+    }
+    let limit = query.limit.unwrap_or(DEFAULT_SUGGESTIONS);
+    if limit == 0 || limit > MAX_SUGGESTIONS {
+        return Err(CustomError::BadRequestWithMsg {
+            message: Message::new("limit-out-of-range").arg("max", MAX_SUGGESTIONS),
+        });
+    }
+    let results = SuggestionsResponse {
+        items: suggest_titles(&conn, prefix, limit).await?,
+    };
+    Ok(negotiated_list_response(&req, StatusCode::OK, &results, &["id", "title", "similarity"], &results.items))
+}
+
+#[derive(Deserialize)]
+pub struct GetTodoQuery {
+    fields: Option<String>,
+}
+
+/// `fields` takes a list of columns such as `id,title,done` and only selects those.
+/// The whole todo carries an `ETag` per format, and is not sent again if `If-None-Match` lists it. Projections carry none.
+#[tracing::instrument(name = "Get todo", skip_all, fields(todo.id = *path, fields = ?query.fields))]
 pub async fn get_todo(
-    conn: web::Data<DbConn>,
+    req: HttpRequest,
+    conn: web::Data<Database>,
     path: web::Path<usize>,
+    query: web::Query<GetTodoQuery>,
 ) -> Result<HttpResponse, CustomError> {
     let id = path.into_inner();
+    if let Some(fields) = &query.fields {
+        let results = find_todo_fields_by_id(&conn, id, &parse_fields(fields)?).await?;
+        return Ok(negotiated_response(&req, StatusCode::OK, &results));
+    }
     let results = find_todo_by_id(&conn, id).await?;
-    Ok(HttpResponse::Ok().json(results))
+    let format = Format::negotiate(&req, &[Format::Json, Format::MessagePack])?;
+    let tag = etag(results.id, &results.updated_at, format);
+    if is_not_modified(&req, &tag)? {
+        return Ok(not_modified(&tag));
+    }
+    let mut res = negotiated_response(&req, StatusCode::OK, &results);
+    insert_etag(&mut res, &tag);
+    Ok(res)
 }
 
-#[post("/todos")]
+#[tracing::instrument(name = "Create todo", skip_all)]
 pub async fn create_todo(
-    conn: web::Data<DbConn>,
-    create_todo_dto: web::Json<CreateTodoDto>,
-) -> Result<CustomResponse, CustomError> {
+    req: HttpRequest,
+    conn: web::Data<Database>,
+    create_todo_dto: Body<CreateTodoDto>,
+) -> Result<HttpResponse, CustomError> {
     if let Err(e) = create_todo_dto.validate() {
-        println!("DOES IT WORK?");
+        tracing::info!(error.message = %e, "Invalid todo");
         return Err(CustomError::ValidationError { e });
     }
     let title = create_todo_dto.title.clone();
     let description = create_todo_dto.description.clone();
     let done = create_todo_dto.done;
-    let resp = insert_todo(&conn, title.as_str(), description.as_str(), done).await?;
-    Ok(resp)
+    let todo = insert_todo(&conn, title.as_str(), description.as_str(), done).await?;
+    let id = todo.id as usize;
+    let mut res = write_response(&req, CustomResponse::Created { id }, &todo, Some(format!("/todos/{}", id)));
+    insert_write_etag(&mut res, todo.id, &todo.updated_at);
+    Ok(res)
 }
 
-#[put("/todos/{id}")]
+/// Only applies if `If-Match` lists the current `ETag` of the todo, when sent. `REQUIRE_IF_MATCH` makes it mandatory.
+#[tracing::instrument(name = "Update todo", skip_all, fields(todo.id = *path))]
 pub async fn update_todo(
-    conn: web::Data<DbConn>,
+    req: HttpRequest,
+    conn: web::Data<Database>,
+    config: web::Data<Settings>,
     path: web::Path<usize>,
-    update_todo_dto: web::Json<UpdateTodoDto>,
-) -> Result<CustomResponse, CustomError> {
+    update_todo_dto: Body<UpdateTodoDto>,
+) -> Result<HttpResponse, CustomError> {
+    let id = path.into_inner();
     if let Err(e) = update_todo_dto.validate() {
+        tracing::info!(error.message = %e, "Invalid todo");
         return Err(CustomError::ValidationError { e });
     }
-    let id = path.into_inner();
     let title = update_todo_dto.title.clone();
     let description = update_todo_dto.description.clone();
     let done = update_todo_dto.done;
-    let resp = update_todo_by_id(&conn, id, title, description, done).await?;
-    Ok(resp)
+    let if_match = if_match(&req, config.require_if_match)?;
+    let todo = update_todo_by_id(&conn, id, title, description, done, if_match.as_ref()).await?;
+    let mut res = write_response(&req, CustomResponse::Updated { id }, &todo, None);
+    insert_write_etag(&mut res, todo.id, &todo.updated_at);
+    Ok(res)
 }
 
-#[delete("/todos/{id}")]
-pub async fn delete_todo(conn: web::Data<DbConn>, path: web::Path<usize>) -> Result<CustomResponse, CustomError> {
+/// Only applies if `If-Match` lists the current `ETag` of the todo, when sent. `REQUIRE_IF_MATCH` makes it mandatory.
+#[tracing::instrument(name = "Delete todo", skip_all, fields(todo.id = *path))]
+pub async fn delete_todo(
+    req: HttpRequest,
+    conn: web::Data<Database>,
+    config: web::Data<Settings>,
+    path: web::Path<usize>,
+) -> Result<CustomResponse, CustomError> {
     let id = path.into_inner();
-    let resp = delete_todo_by_id(&conn, id).await?;
+    let if_match = if_match(&req, config.require_if_match)?;
+    let resp = delete_todo_by_id(&conn, id, if_match.as_ref()).await?;
     Ok(resp)
 }
 
-#[delete("/todos")]
-pub async fn bulk_delete_todos(conn: web::Data<DbConn>, ids: web::Json<Vec<usize>>) -> Result<CustomResponse, CustomError> {
+#[tracing::instrument(name = "Bulk delete todos", skip_all, fields(todos.count = ids.len()))]
+pub async fn bulk_delete_todos(conn: web::Data<Database>, ids: Body<Vec<usize>>) -> Result<CustomResponse, CustomError> {
     let resp = bulk_delete_todos_by_ids(&conn, ids.into_inner()).await?;
     Ok(resp)
 }
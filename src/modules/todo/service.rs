@@ -1,88 +1,685 @@
-use migration::DbErr;
-use sea_orm::{query::*, ActiveModelTrait, ColumnTrait, DbConn, EntityTrait, Set, ModelTrait};
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::sea_query::Expr;
+use sea_orm::{DbErr, FromQueryResult, Iterable, JsonValue, QueryResult, SelectModel, Selector};
+use serde::Serialize;
+use sea_orm::{query::*, ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, EntityTrait, IdenStatic, Set, ModelTrait};
 
-use crate::shared::errors::CustomError;
-use crate::shared::responses::CustomResponse;
+use crate::core::database::{Database, InstrumentedConnection};
+use crate::shared::error_codes::ErrorCode;
+use crate::shared::cursor::Cursor;
+use crate::shared::errors::{ConflictDetails, CustomError};
+use crate::shared::etag::check_if_match;
+use crate::shared::filter::{FieldType, FilterField};
+use crate::shared::i18n::Message;
+use crate::shared::responses::{CursorPage, CustomResponse, Page};
+use actix_web::http::header::IfMatch;
 use entity::todo;
 
-pub async fn find_todos(
-    conn: &DbConn,
-    query_string: Option<String>,
+/// The page size used when the client does not ask for one.
+pub const DEFAULT_ITEMS_PER_PAGE: usize = 10;
+
+/// The unique constraint on `todo.title`.
+const TITLE_CONSTRAINT: &str = "todo_title_key";
+
+/// Turns a violation of the unique title constraint into a conflict pointing at the todo that holds the title.
+/// Todos are not owned by users, so revealing the existing todo leaks nothing the client could not list itself.
+async fn title_conflict<C: ConnectionTrait>(conn: &C, title: &str, e: CustomError) -> CustomError {
+    match &e {
+        CustomError::UniqueViolation { constraint } if constraint == TITLE_CONSTRAINT => {}
+        _ => return e,
+    }
+
+    let existing = todo::Entity::find()
+        .filter(todo::Column::Title.eq(title))
+        .one(conn)
+        .await
+        .unwrap_or_default();
+
+    CustomError::Duplicate {
+        conflict: ConflictDetails {
+            field: "title".to_owned(),
+            constraint: TITLE_CONSTRAINT.to_owned(),
+            existing_id: existing.as_ref().map(|todo| todo.id),
+            existing_url: existing.map(|todo| format!("/todos/{}", todo.id)),
+        },
+    }
+}
+
+/// Narrows the list of todos. Every filter that is set must match.
+#[derive(Debug, Default)]
+pub struct TodoFilter {
+    /// Text the title contains.
+    pub query_string: Option<String>,
+    /// Text the description contains.
+    pub description: Option<String>,
+    pub done: Option<bool>,
+    /// Inclusive lower bound of `created_at`.
+    pub created_from: Option<DateTimeWithTimeZone>,
+    /// Exclusive upper bound of `created_at`.
+    pub created_to: Option<DateTimeWithTimeZone>,
+    /// Inclusive lower bound of `updated_at`.
+    pub updated_from: Option<DateTimeWithTimeZone>,
+    /// Exclusive upper bound of `updated_at`.
+    pub updated_to: Option<DateTimeWithTimeZone>,
+    /// A compiled `filter` expression, see `shared::filter`.
+    pub expression: Option<Condition>,
+}
+
+/// The fields a `filter` expression can refer to.
+pub const FILTER_FIELDS: &[FilterField<todo::Column>] = &[
+    FilterField { name: "id", column: todo::Column::Id, field_type: FieldType::Integer },
+    FilterField { name: "title", column: todo::Column::Title, field_type: FieldType::Text },
+    FilterField { name: "description", column: todo::Column::Description, field_type: FieldType::Text },
+    FilterField { name: "done", column: todo::Column::Done, field_type: FieldType::Boolean },
+    FilterField { name: "created_at", column: todo::Column::CreatedAt, field_type: FieldType::Timestamp },
+    FilterField { name: "updated_at", column: todo::Column::UpdatedAt, field_type: FieldType::Timestamp },
+];
+
+impl TodoFilter {
+    fn apply(&self, mut stmt: Select<todo::Entity>) -> Select<todo::Entity> {
+        if let Some(query_string) = &self.query_string {
+            stmt = stmt.filter(todo::Column::Title.contains(query_string.as_str()));
+        }
+        if let Some(description) = &self.description {
+            stmt = stmt.filter(todo::Column::Description.contains(description.as_str()));
+        }
+        if let Some(done) = self.done {
+            stmt = stmt.filter(todo::Column::Done.eq(done));
+        }
+        if let Some(created_from) = self.created_from {
+            stmt = stmt.filter(todo::Column::CreatedAt.gte(created_from));
+        }
+        if let Some(created_to) = self.created_to {
+            stmt = stmt.filter(todo::Column::CreatedAt.lt(created_to));
+        }
+        if let Some(updated_from) = self.updated_from {
+            stmt = stmt.filter(todo::Column::UpdatedAt.gte(updated_from));
+        }
+        if let Some(updated_to) = self.updated_to {
+            stmt = stmt.filter(todo::Column::UpdatedAt.lt(updated_to));
+        }
+        if let Some(expression) = &self.expression {
+            stmt = stmt.filter(expression.clone());
+        }
+        stmt
+    }
+}
+
+/// One column of the order of the list.
+#[derive(Debug, Clone)]
+pub struct SortKey {
+    pub column: todo::Column,
+    pub order: Order,
+}
+
+/// The columns clients can sort by and select, as named in the `sort` and `fields` query parameters.
+const COLUMNS: &[(&str, todo::Column)] = &[
+    ("id", todo::Column::Id),
+    ("title", todo::Column::Title),
+    ("description", todo::Column::Description),
+    ("done", todo::Column::Done),
+    ("created_at", todo::Column::CreatedAt),
+    ("updated_at", todo::Column::UpdatedAt),
+];
+
+/// Parses a comma-separated list of columns, each optionally prefixed with `-` to sort it in descending order,
+/// e.g. `done,-updated_at`.
+pub fn parse_sort(sort: &str) -> Result<Vec<SortKey>, CustomError> {
+    let mut keys: Vec<SortKey> = vec![];
+    for field in sort.split(',').map(str::trim).filter(|field| !field.is_empty()) {
+        let (name, order) = match field.strip_prefix('-') {
+            Some(name) => (name, Order::Desc),
+            None => (field, Order::Asc),
+        };
+        let column = COLUMNS
+            .iter()
+            .find(|(sortable, _)| *sortable == name)
+            .map(|(_, column)| *column)
+            .ok_or_else(|| CustomError::BadRequestWithMsg {
+                message: Message::new("unknown-sort-column")
+                    .arg("column", name)
+                    .arg("expected", COLUMNS.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(", ")),
+            })?;
+        if keys.iter().any(|key| key.column.as_str() == column.as_str()) {
+            return Err(CustomError::BadRequestWithMsg {
+                message: Message::new("duplicate-sort-column").arg("column", name),
+            });
+        }
+        keys.push(SortKey { column, order });
+    }
+    Ok(keys)
+}
+
+/// Parses a comma-separated list of columns to select, e.g. `id,title,done`.
+pub fn parse_fields(fields: &str) -> Result<Vec<todo::Column>, CustomError> {
+    let mut columns: Vec<todo::Column> = vec![];
+    for name in fields.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+        let column = COLUMNS
+            .iter()
+            .find(|(selectable, _)| *selectable == name)
+            .map(|(_, column)| *column)
+            .ok_or_else(|| CustomError::BadRequestWithMsg {
+                message: Message::new("unknown-field")
+                    .arg("field", name)
+                    .arg("expected", COLUMNS.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(", ")),
+            })?;
+        if !columns.iter().any(|selected| selected.as_str() == column.as_str()) {
+            columns.push(column);
+        }
+    }
+    if columns.is_empty() {
+        return Err(CustomError::BadRequestWithMsg {
+            message: Message::new("no-fields"),
+        });
+    }
+    Ok(columns)
+}
+
+/// A row of the todo list: a whole `todo::Model`, or a JSON object with only the fields the client asked for.
+pub trait TodoRow: FromQueryResult + Serialize + Sized + Send + Sync {
+    /// Selects the columns of the rows. `fields` is empty when every column is wanted.
+    fn select(stmt: Select<todo::Entity>, fields: &[todo::Column]) -> Selector<SelectModel<Self>>;
+
+    /// The position of the row for keyset pagination.
+    fn cursor(&self) -> Option<Cursor>;
+
+    /// Drops the columns that were only selected to compute cursors.
+    fn project(self, fields: &[todo::Column]) -> Self;
+}
+
+impl TodoRow for todo::Model {
+    fn select(stmt: Select<todo::Entity>, _fields: &[todo::Column]) -> Selector<SelectModel<Self>> {
+        stmt.into_model()
+    }
+
+    fn cursor(&self) -> Option<Cursor> {
+        Some(Cursor::new(self.created_at, self.id))
+    }
+
+    fn project(self, _fields: &[todo::Column]) -> Self {
+        self
+    }
+}
+
+/// A todo with only the fields the client asked for. Its position is read from the typed columns rather than from
+/// the JSON object, which carries the timestamp in whatever form the driver renders it.
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(transparent)]
+pub struct TodoFields {
+    pub fields: JsonValue,
+    #[serde(skip)]
+    cursor: Cursor,
+}
+
+impl FromQueryResult for TodoFields {
+    fn from_query_result(res: &QueryResult, pre: &str) -> Result<Self, DbErr> {
+        Ok(Self {
+            fields: JsonValue::from_query_result(res, pre)?,
+            cursor: Cursor::new(res.try_get(pre, "created_at")?, res.try_get(pre, "id")?),
+        })
+    }
+}
+
+impl TodoRow for TodoFields {
+    fn select(stmt: Select<todo::Entity>, fields: &[todo::Column]) -> Selector<SelectModel<Self>> {
+        if fields.is_empty() {
+            return stmt.into_model();
+        }
+        let mut stmt = stmt.select_only();
+        for column in fields {
+            stmt = stmt.column(*column);
+        }
+        for column in [todo::Column::Id, todo::Column::CreatedAt] {
+            if !fields.iter().any(|field| field.as_str() == column.as_str()) {
+                stmt = stmt.column(column);
+            }
+        }
+        stmt.into_model()
+    }
+
+    fn cursor(&self) -> Option<Cursor> {
+        Some(self.cursor.clone())
+    }
+
+    fn project(mut self, fields: &[todo::Column]) -> Self {
+        if fields.is_empty() {
+            return self;
+        }
+        if let Some(row) = self.fields.as_object_mut() {
+            row.retain(|name, _| fields.iter().any(|field| field.as_str() == name.as_str()));
+        }
+        self
+    }
+}
+
+/// The header row of todos written as CSV: the selected `fields`, or every column.
+fn csv_columns(fields: &[todo::Column]) -> Vec<String> {
+    if fields.is_empty() {
+        return todo::Column::iter().map(|column| column.as_str().to_owned()).collect();
+    }
+    fields.iter().map(|column| column.as_str().to_owned()).collect()
+}
+
+/// Orders by the given keys, or most recently created first if there are none. Ties are broken by id, in the
+/// direction of the last key, so that pages never overlap. The default order is on columns no update changes, so
+/// editing a todo does not move it to another page.
+fn apply_sort(mut stmt: Select<todo::Entity>, sort: &[SortKey]) -> Select<todo::Entity> {
+    let default_sort = [SortKey { column: todo::Column::CreatedAt, order: Order::Desc }];
+    let sort = if sort.is_empty() { &default_sort[..] } else { sort };
+    for key in sort {
+        stmt = stmt.order_by(key.column, key.order.clone());
+    }
+    if !sort.iter().any(|key| matches!(key.column, todo::Column::Id)) {
+        let order = sort.last().map(|key| key.order.clone()).unwrap_or(Order::Desc);
+        stmt = stmt.order_by(todo::Column::Id, order);
+    }
+    stmt
+}
+
+#[tracing::instrument(
+    name = "Find todos",
+    skip_all,
+    fields(filter = ?filter, sort = ?sort, fields = ?fields, page = ?page_num, items_per_page = ?items_per_page, todos.count = tracing::field::Empty)
+)]
+pub async fn find_todos<R: TodoRow>(
+    db: &Database,
+    filter: &TodoFilter,
+    sort: &[SortKey],
+    fields: &[todo::Column],
     items_per_page: Option<usize>,
     page_num: Option<usize>,
-) -> Result<Vec<todo::Model>, CustomError> {
-    let mut stmt = todo::Entity::find();
+) -> Result<Page<R>, CustomError> {
+    let conn = &db.instrumented();
+    let stmt = apply_sort(filter.apply(todo::Entity::find()), sort);
+
+    let page = page_num.unwrap_or(0);
+    let per_page = items_per_page.unwrap_or(DEFAULT_ITEMS_PER_PAGE);
+    let paginator = R::select(stmt, fields).paginate(conn, per_page);
+    let total_items = paginator.num_items().await?;
+    let results = paginator.fetch_page(page).await?;
+
+    tracing::Span::current().record("todos.count", results.len());
+    // Cursors follow the default order only.
+    let has_next = page
+        .checked_add(1)
+        .and_then(|next| next.checked_mul(per_page))
+        .is_some_and(|end| end < total_items);
+    let next_cursor = results
+        .last()
+        .filter(|_| sort.is_empty() && has_next)
+        .and_then(R::cursor);
+    let results = results.into_iter().map(|row| row.project(fields)).collect();
+    let mut page = Page::new(results, page, per_page, total_items, csv_columns(fields));
+    page.next_cursor = next_cursor.map(|cursor| cursor.encode());
+    Ok(page)
+}
+
+/// The text search configuration of the `todo.search` column.
+/// ❗ Keep this in sync with the migration that adds the column, or searches stop using its index.
+const SEARCH_CONFIG: &str = "english";
+
+/// A todo matching a full-text search, with how well it matches and where.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct TodoSearchHit {
+    #[serde(flatten)]
+    pub todo: todo::Model,
+    pub rank: f32,
+    pub highlights: TodoHighlights,
+}
+
+/// The title and description with matching words wrapped in `<mark>` tags. The rest of the text is HTML-escaped,
+/// so highlights can be rendered as HTML.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct TodoHighlights {
+    pub title: String,
+    pub description: String,
+}
+
+#[derive(FromQueryResult)]
+struct TodoSearchRow {
+    id: i32,
+    title: String,
+    description: String,
+    done: bool,
+    created_at: DateTimeWithTimeZone,
+    updated_at: DateTimeWithTimeZone,
+    rank: f32,
+    title_highlight: String,
+    description_highlight: String,
+}
 
-    if let Some(query_string) = query_string {
-        stmt = stmt.filter(todo::Column::Title.contains(query_string.as_str()));
+impl From<TodoSearchRow> for TodoSearchHit {
+    fn from(row: TodoSearchRow) -> Self {
+        Self {
+            todo: todo::Model {
+                id: row.id,
+                title: row.title,
+                description: row.description,
+                done: row.done,
+                created_at: row.created_at,
+                updated_at: row.updated_at,
+            },
+            rank: row.rank,
+            highlights: TodoHighlights {
+                title: row.title_highlight,
+                description: row.description_highlight,
+            },
+        }
     }
+}
 
-    let results = stmt
-        .order_by_desc(todo::Column::UpdatedAt)
-        .paginate(conn, items_per_page.unwrap_or(10))
-        .fetch_page(page_num.unwrap_or(0))
-        .await
-        .map_err(|_| CustomError::ServerError)?;
+/// HTML-escapes a column of `todo` in SQL. `ts_headline` runs over the escaped text, so the `<mark>` tags it adds
+/// are the only markup in a highlight.
+fn html_escaped(column: &str) -> String {
+    let mut sql = format!(r#""todo"."{}""#, column);
+    for (from, to) in [("&", "&amp;"), ("<", "&lt;"), (">", "&gt;"), ("\"", "&quot;"), ("''", "&#39;")] {
+        sql = format!("replace({}, '{}', '{}')", sql, from, to);
+    }
+    sql
+}
+
+/// Searches the words of the title and description with `websearch_to_tsquery`, which understands quoted
+/// phrases, `or` and `-word`. Hits are ranked by relevance unless `sort` is given.
+#[tracing::instrument(
+    name = "Search todos",
+    skip_all,
+    fields(filter = ?filter, sort = ?sort, page = ?page_num, items_per_page = ?items_per_page, todos.count = tracing::field::Empty)
+)]
+pub async fn search_todos(
+    db: &Database,
+    q: &str,
+    filter: &TodoFilter,
+    sort: &[SortKey],
+    items_per_page: Option<usize>,
+    page_num: Option<usize>,
+) -> Result<Page<TodoSearchHit>, CustomError> {
+    let conn = &db.instrumented();
+    let query = format!("websearch_to_tsquery('{}', $1)", SEARCH_CONFIG);
+    let headline = |column: &str, options: &str| {
+        Expr::cust_with_values(
+            &format!("ts_headline('{}', {}, {}, '{}')", SEARCH_CONFIG, html_escaped(column), query, options),
+            [q],
+        )
+    };
+    let mut stmt = filter
+        .apply(todo::Entity::find())
+        .filter(Expr::cust_with_values(&format!(r#""todo"."search" @@ {}"#, query), [q]))
+        .column_as(Expr::cust_with_values(&format!(r#"ts_rank("todo"."search", {})"#, query), [q]), "rank")
+        .column_as(headline("title", "StartSel=<mark>, StopSel=</mark>, HighlightAll=true"), "title_highlight")
+        .column_as(headline("description", "StartSel=<mark>, StopSel=</mark>"), "description_highlight");
+    stmt = if sort.is_empty() {
+        stmt.order_by_desc(Expr::cust(r#""rank""#))
+            .order_by_desc(todo::Column::Id)
+    } else {
+        apply_sort(stmt, sort)
+    };
+
+    let page = page_num.unwrap_or(0);
+    let per_page = items_per_page.unwrap_or(DEFAULT_ITEMS_PER_PAGE);
+    let paginator = stmt.into_model::<TodoSearchRow>().paginate(conn, per_page);
+    let total_items = paginator.num_items().await?;
+    let results: Vec<TodoSearchHit> = paginator
+        .fetch_page(page)
+        .await?
+        .into_iter()
+        .map(TodoSearchHit::from)
+        .collect();
+
+    tracing::Span::current().record("todos.count", results.len());
+    let mut columns = csv_columns(&[]);
+    columns.extend(["rank".to_owned(), "highlights".to_owned()]);
+    Ok(Page::new(results, page, per_page, total_items, columns))
+}
+
+/// Where to read a keyset page from, relative to a cursor.
+#[derive(Debug)]
+pub enum CursorDirection {
+    /// The todos listed after the cursor.
+    After(Cursor),
+    /// The todos listed before the cursor.
+    Before(Cursor),
+}
+
+/// The todos listed after `cursor`: created earlier, or at the same time with a lower id.
+fn listed_after(cursor: &Cursor) -> Condition {
+    Condition::any()
+        .add(todo::Column::CreatedAt.lt(cursor.timestamp))
+        .add(
+            Condition::all()
+                .add(todo::Column::CreatedAt.eq(cursor.timestamp))
+                .add(todo::Column::Id.lt(cursor.id)),
+        )
+}
+
+/// The todos listed before `cursor`: created later, or at the same time with a higher id.
+fn listed_before(cursor: &Cursor) -> Condition {
+    Condition::any()
+        .add(todo::Column::CreatedAt.gt(cursor.timestamp))
+        .add(
+            Condition::all()
+                .add(todo::Column::CreatedAt.eq(cursor.timestamp))
+                .add(todo::Column::Id.gt(cursor.id)),
+        )
+}
+
+/// Returns `cursor` if some todo matching `filter` is on the given `side` of it, so that a page only links to
+/// neighbours that exist.
+async fn cursor_if_any<C: ConnectionTrait>(
+    conn: &C,
+    filter: &TodoFilter,
+    cursor: Option<Cursor>,
+    side: fn(&Cursor) -> Condition,
+) -> Result<Option<Cursor>, CustomError> {
+    let cursor = match cursor {
+        Some(cursor) => cursor,
+        None => return Ok(None),
+    };
+    let found = filter
+        .apply(todo::Entity::find())
+        .select_only()
+        .column(todo::Column::Id)
+        .filter(side(&cursor))
+        .into_json()
+        .one(conn)
+        .await?;
+    Ok(found.map(|_| cursor))
+}
+
+/// Lists todos by keyset in the same order as `find_todos`: most recently created first, ties broken by id.
+/// Unlike offsets, cursors neither skip nor repeat todos when others are added, removed or updated between requests,
+/// as neither key ever changes.
+#[tracing::instrument(
+    name = "Find todos by cursor",
+    skip_all,
+    fields(filter = ?filter, fields = ?fields, direction = ?direction, items_per_page = ?items_per_page, todos.count = tracing::field::Empty)
+)]
+pub async fn find_todos_by_cursor<R: TodoRow>(
+    db: &Database,
+    filter: &TodoFilter,
+    fields: &[todo::Column],
+    items_per_page: Option<usize>,
+    direction: CursorDirection,
+) -> Result<CursorPage<R>, CustomError> {
+    let conn = &db.instrumented();
+    let stmt = filter.apply(todo::Entity::find());
+
+    let per_page = items_per_page.unwrap_or(DEFAULT_ITEMS_PER_PAGE);
+    // One extra row tells whether there is another page in the direction of travel.
+    let limit = per_page as u64 + 1;
+    let (results, next_cursor, prev_cursor) = match direction {
+        CursorDirection::After(cursor) => {
+            let stmt = stmt
+                .filter(listed_after(&cursor))
+                .order_by_desc(todo::Column::CreatedAt)
+                .order_by_desc(todo::Column::Id)
+                .limit(limit);
+            let mut results = R::select(stmt, fields).all(conn).await?;
+            let has_more = results.len() > per_page;
+            results.truncate(per_page);
+            let next_cursor = results.last().filter(|_| has_more).and_then(R::cursor);
+            // The previous page ends at the first row, or at the cursor if this page is empty, and only exists
+            // if some todo is listed before it.
+            let first = match results.first() {
+                Some(first) => first.cursor(),
+                None => Some(cursor),
+            };
+            let prev_cursor = cursor_if_any(conn, filter, first, listed_before).await?;
+            (results, next_cursor, prev_cursor)
+        }
+        CursorDirection::Before(cursor) => {
+            let stmt = stmt
+                .filter(listed_before(&cursor))
+                .order_by_asc(todo::Column::CreatedAt)
+                .order_by_asc(todo::Column::Id)
+                .limit(limit);
+            let mut results = R::select(stmt, fields).all(conn).await?;
+            let has_more = results.len() > per_page;
+            results.truncate(per_page);
+            results.reverse();
+            let prev_cursor = results.first().filter(|_| has_more).and_then(R::cursor);
+            let last = match results.last() {
+                Some(last) => last.cursor(),
+                None => Some(cursor),
+            };
+            let next_cursor = cursor_if_any(conn, filter, last, listed_after).await?;
+            (results, next_cursor, prev_cursor)
+        }
+    };
+
+    tracing::Span::current().record("todos.count", results.len());
+    Ok(CursorPage::new(
+        results.into_iter().map(|row| row.project(fields)).collect(),
+        per_page,
+        next_cursor.map(|cursor| cursor.encode()),
+        prev_cursor.map(|cursor| cursor.encode()),
+        csv_columns(fields),
+    ))
+}
+
+/// A title close to what the client typed, scored between 0 and 1.
+#[derive(FromQueryResult, Serialize, Debug, PartialEq)]
+pub struct TodoSuggestion {
+    pub id: i32,
+    pub title: String,
+    pub similarity: f32,
+}
 
+/// Escapes the wildcards of a `LIKE` pattern.
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+/// Suggests titles starting with or resembling `prefix`, best matches first. Both conditions are served by the
+/// trigram index on `todo.title`, so this is cheap enough to call on every keystroke.
+/// Word similarity tolerates typos and scores how well `prefix` matches some part of the title, which also makes
+/// it suited to warning about near-duplicates before creating a todo.
+#[tracing::instrument(name = "Suggest todo titles", skip_all, fields(limit = limit, todos.count = tracing::field::Empty))]
+pub async fn suggest_titles(db: &Database, prefix: &str, limit: usize) -> Result<Vec<TodoSuggestion>, CustomError> {
+    let conn = &db.instrumented();
+    let results = todo::Entity::find()
+        .select_only()
+        .column(todo::Column::Id)
+        .column(todo::Column::Title)
+        .column_as(Expr::cust_with_values(r#"word_similarity($1, "todo"."title")"#, [prefix]), "similarity")
+        .filter(
+            Condition::any()
+                .add(Expr::cust_with_values(r#"$1 <% "todo"."title""#, [prefix]))
+                .add(Expr::cust_with_values(r#""todo"."title" ILIKE $1"#, [format!("{}%", escape_like(prefix))])),
+        )
+        .order_by_desc(Expr::cust(r#""similarity""#))
+        .order_by_asc(todo::Column::Title)
+        .limit(limit as u64)
+        .into_model::<TodoSuggestion>()
+        .all(conn)
+        .await?;
+
+    tracing::Span::current().record("todos.count", results.len());
     Ok(results)
 }
 
-pub async fn find_todo_by_id(conn: &DbConn, id: usize) -> Result<todo::Model, CustomError> {
+#[tracing::instrument(name = "Find todo by id", skip_all, fields(todo.id = id))]
+pub async fn find_todo_by_id(db: &Database, id: usize) -> Result<todo::Model, CustomError> {
+    let conn = &db.instrumented();
     let result = todo::Entity::find_by_id(id as i32)
         .one(conn)
-        .await
-        .map_err(|_| CustomError::ServerError)?;
+        .await?;
 
     if result.is_none() {
-        return Err(CustomError::NotFound);
+        return Err(CustomError::NotFoundWithCode { code: ErrorCode::TodoNotFound });
     }
 
     Ok(result.unwrap())
 }
 
+/// Finds a todo with only the given fields.
+#[tracing::instrument(name = "Find todo fields by id", skip_all, fields(todo.id = id, fields = ?fields))]
+pub async fn find_todo_fields_by_id(
+    db: &Database,
+    id: usize,
+    fields: &[todo::Column],
+) -> Result<JsonValue, CustomError> {
+    let conn = &db.instrumented();
+    let stmt = todo::Entity::find_by_id(id as i32);
+    let result = TodoFields::select(stmt, fields).one(conn).await?;
+
+    match result {
+        Some(result) => Ok(result.project(fields).fields),
+        None => Err(CustomError::NotFoundWithCode { code: ErrorCode::TodoNotFound }),
+    }
+}
+
+#[tracing::instrument(name = "Insert todo", skip_all, fields(todo.id = tracing::field::Empty, todo.done = done))]
 pub async fn insert_todo(
-    conn: &DbConn,
+    db: &Database,
     title: &str,
     description: &str,
     done: bool,
-) -> Result<CustomResponse, CustomError> {
-    let res = todo::Entity::insert(todo::ActiveModel {
+) -> Result<todo::Model, CustomError> {
+    let conn = &db.instrumented();
+    let res = todo::ActiveModel {
         title: Set(title.to_string()),
         description: Set(description.to_string()),
         done: Set(done),
         ..Default::default()
-    })
-    .exec(conn)
-    .await
-    .map_err(|e| {
-        match e {
-            DbErr::Query(..) => CustomError::Conflict,
-            _ => CustomError::ServerError,
-        }
-    })?;
+    }
+    .insert(conn)
+    .await;
 
-    Ok(CustomResponse::Created {id: res.last_insert_id as usize})
+    let todo = match res {
+        Ok(todo) => todo,
+        Err(e) => return Err(title_conflict(conn, title, CustomError::from(e)).await),
+    };
+
+    tracing::Span::current().record("todo.id", todo.id);
+    Ok(todo)
 }
 
+/// Updates a todo. With `if_match`, fails with 412 unless it lists the current ETag of the todo, and the update
+/// only applies if the todo was not changed in the meantime.
+#[tracing::instrument(name = "Update todo", skip_all, fields(todo.id = id, todo.done = ?done))]
 pub async fn update_todo_by_id(
-    conn: &DbConn,
+    db: &Database,
     id: usize,
     title: Option<String>,
     description: Option<String>,
     done: Option<bool>,
-) -> Result<CustomResponse, CustomError> {
+    if_match: Option<&IfMatch>,
+) -> Result<todo::Model, CustomError> {
+    let conn = &db.instrumented();
     let todo = todo::Entity::find_by_id(id as i32)
         .one(conn)
-        .await
-        .map_err(|_| CustomError::ServerError)?;
+        .await?;
 
     if todo.is_none() {
-        return Err(CustomError::NotFound);
+        return Err(CustomError::NotFoundWithCode { code: ErrorCode::TodoNotFound });
     }
 
-    let mut todo: todo::ActiveModel = todo.unwrap().into();
+    let found = todo.unwrap();
+    check_if_match(if_match, found.id, &found.updated_at)?;
+    let updated_at = found.updated_at;
+    let mut todo: todo::ActiveModel = found.into();
 
-    if let Some(title) = title {
+    if let Some(title) = title.clone() {
         todo.title = Set(title);
     }
 
@@ -94,42 +691,66 @@ pub async fn update_todo_by_id(
         todo.done = Set(done);
     }
 
-    todo.update(conn)
-        .await
-        .map_err(|e| {
-            println!("Updated error: {:?}", e);
-            CustomError::ServerError
-        })?;
-
-    Ok(CustomResponse::Updated { id })
+    let mut stmt = todo::Entity::update(todo);
+    if if_match.is_some() {
+        stmt = stmt.filter(todo::Column::UpdatedAt.eq(updated_at));
+    }
+    match stmt.exec(conn).await {
+        Ok(todo) => Ok(todo),
+        // The todo was updated or deleted since it was read.
+        Err(DbErr::RecordNotFound(_)) if if_match.is_some() => Err(CustomError::PreconditionFailed),
+        Err(e) => {
+            let e = CustomError::from(e);
+            Err(match title {
+                Some(title) => title_conflict(conn, &title, e).await,
+                None => e,
+            })
+        }
+    }
 }
 
-pub async fn delete_todo_by_id(conn: &DbConn, id: usize) -> Result<CustomResponse, CustomError> {
-    let found: todo::Model = find_todo_by_id(conn, id).await?;
+/// Deletes a todo. With `if_match`, fails with 412 unless it lists the current ETag of the todo, and the delete
+/// only applies if the todo was not changed in the meantime.
+#[tracing::instrument(name = "Delete todo", skip_all, fields(todo.id = id))]
+pub async fn delete_todo_by_id(
+    db: &Database,
+    id: usize,
+    if_match: Option<&IfMatch>,
+) -> Result<CustomResponse, CustomError> {
+    let found: todo::Model = find_todo_by_id(db, id).await?;
+    check_if_match(if_match, found.id, &found.updated_at)?;
+    let conn = &db.instrumented();
 
-    found.delete(conn).await.map_err(|_| CustomError::ServerError)?;
+    if if_match.is_none() {
+        found.delete(conn).await?;
+        return Ok(CustomResponse::Deleted { id });
+    }
+
+    let res = todo::Entity::delete_many()
+        .filter(todo::Column::Id.eq(found.id))
+        .filter(todo::Column::UpdatedAt.eq(found.updated_at))
+        .exec(conn)
+        .await?;
+    if res.rows_affected == 0 {
+        return Err(CustomError::PreconditionFailed);
+    }
 
     Ok(CustomResponse::Deleted { id })
 }
 
+#[tracing::instrument(name = "Bulk delete todos", skip_all, fields(todos.ids = ?ids, todos.count = ids.len()))]
 pub async fn bulk_delete_todos_by_ids(
-    conn: &DbConn,
+    db: &Database,
     ids: Vec<usize>,
 ) -> Result<CustomResponse, CustomError> {
-    let txn = conn.begin().await.map_err(|e| {
-        println!("Transaction error: {:?}", e);
-        CustomError::ServerError
-    })?;
+    let txn = db.conn.begin().await?;
 
     for id in ids.clone() {
-        let found: todo::Model = find_todo_by_id(conn, id).await?;
-        found.delete(&txn).await.map_err(|_| CustomError::ServerError)?;
+        let found: todo::Model = find_todo_by_id(db, id).await?;
+        found.delete(&InstrumentedConnection::new(&txn, db.slow_query_threshold)).await?;
     }
 
-    txn.commit().await.map_err(|e| {
-        println!("Transaction error: {:?}", e);
-        CustomError::ServerError
-    })?;
+    txn.commit().await?;
 
     Ok(CustomResponse::BulkDeleted { ids })
 }
@@ -137,14 +758,22 @@ pub async fn bulk_delete_todos_by_ids(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::shared::etag::etag;
+    use crate::shared::responses::Format;
     use chrono::{FixedOffset, TimeZone};
     use entity::todo;
-    use sea_orm::{DatabaseBackend, MockDatabase, MockExecResult, Transaction};
+    use sea_orm::{DatabaseBackend, MockDatabase, MockExecResult, Transaction, Value};
+    use std::collections::BTreeMap;
+
+    fn num_items(count: i64) -> BTreeMap<&'static str, Value> {
+        BTreeMap::from([("num_items", Value::BigInt(Some(count)))])
+    }
 
     #[async_std::test]
     async fn test_find_todos() -> Result<(), CustomError> {
         let datetime = FixedOffset::east(0).ymd(2016, 11, 08).and_hms(0, 0, 0);
-        let db = MockDatabase::new(DatabaseBackend::Postgres)
+        let db: Database = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results(vec![vec![num_items(2)]])
             .append_query_results(vec![
                 // First query result
                 vec![
@@ -165,6 +794,9 @@ mod tests {
                         updated_at: datetime,
                     },
                 ],
+            ])
+            .append_query_results(vec![vec![num_items(2)]])
+            .append_query_results(vec![
                 // Second query result
                 vec![
                     todo::Model {
@@ -184,6 +816,9 @@ mod tests {
                         updated_at: datetime,
                     },
                 ],
+            ])
+            .append_query_results(vec![vec![num_items(6)]])
+            .append_query_results(vec![
                 // Third query result
                 vec![todo::Model {
                     id: 1,
@@ -194,11 +829,17 @@ mod tests {
                     updated_at: datetime,
                 }],
             ])
-            .into_connection();
+            .into_connection()
+            .into();
+
+        let apples = TodoFilter {
+            query_string: Some("Apple".to_owned()),
+            ..Default::default()
+        };
 
         // testing find_todos with no query string
         assert_eq!(
-            find_todos(&db, None, None, None).await?,
+            find_todos::<todo::Model>(&db, &TodoFilter::default(), &[], &[], None, None).await?.items,
             vec![
                 todo::Model {
                     id: 1,
@@ -221,7 +862,7 @@ mod tests {
 
         // testing find_todos with query string
         assert_eq!(
-            find_todos(&db, Some("Apple".to_owned()), None, None).await?,
+            find_todos::<todo::Model>(&db, &apples, &[], &[], None, None).await?.items,
             vec![
                 todo::Model {
                     id: 1,
@@ -243,8 +884,10 @@ mod tests {
         );
 
         // testing find_todos with query string and pagination
+        let page = find_todos::<todo::Model>(&db, &apples, &[], &[], Some(5), Some(1)).await?;
+        assert_eq!((page.page, page.per_page, page.total_items, page.total_pages), (1, 5, 6, 2));
         assert_eq!(
-            find_todos(&db, Some("Apple".to_owned()), Some(5), Some(1)).await?,
+            page.items,
             vec![todo::Model {
                 id: 1,
                 title: "Apple pie".to_owned(),
@@ -257,21 +900,36 @@ mod tests {
 
         // Checking transaction log
         assert_eq!(
-            db.into_transaction_log(),
+            db.conn.into_transaction_log(),
             vec![
                 Transaction::from_sql_and_values(
                     DatabaseBackend::Postgres,
-                    r#"SELECT "todo"."id", "todo"."title", "todo"."description", "todo"."done", "todo"."created_at", "todo"."updated_at" FROM "todo" ORDER BY "todo"."updated_at" DESC LIMIT $1 OFFSET $2"#,
+                    r#"SELECT COUNT(*) AS num_items FROM (SELECT "todo"."id", "todo"."title", "todo"."description", "todo"."done", "todo"."created_at", "todo"."updated_at" FROM "todo" ORDER BY "todo"."created_at" DESC, "todo"."id" DESC) AS "sub_query""#,
+                    vec![]
+                ),
+                Transaction::from_sql_and_values(
+                    DatabaseBackend::Postgres,
+                    r#"SELECT "todo"."id", "todo"."title", "todo"."description", "todo"."done", "todo"."created_at", "todo"."updated_at" FROM "todo" ORDER BY "todo"."created_at" DESC, "todo"."id" DESC LIMIT $1 OFFSET $2"#,
                     vec![10u64.into(), 0u64.into()]
                 ),
                 Transaction::from_sql_and_values(
                     DatabaseBackend::Postgres,
-                    r#"SELECT "todo"."id", "todo"."title", "todo"."description", "todo"."done", "todo"."created_at", "todo"."updated_at" FROM "todo" WHERE "todo"."title" LIKE $1 ORDER BY "todo"."updated_at" DESC LIMIT $2 OFFSET $3"#,
+                    r#"SELECT COUNT(*) AS num_items FROM (SELECT "todo"."id", "todo"."title", "todo"."description", "todo"."done", "todo"."created_at", "todo"."updated_at" FROM "todo" WHERE "todo"."title" LIKE $1 ORDER BY "todo"."created_at" DESC, "todo"."id" DESC) AS "sub_query""#,
+                    vec!["%Apple%".into()]
+                ),
+                Transaction::from_sql_and_values(
+                    DatabaseBackend::Postgres,
+                    r#"SELECT "todo"."id", "todo"."title", "todo"."description", "todo"."done", "todo"."created_at", "todo"."updated_at" FROM "todo" WHERE "todo"."title" LIKE $1 ORDER BY "todo"."created_at" DESC, "todo"."id" DESC LIMIT $2 OFFSET $3"#,
                     vec!["%Apple%".into(), 10u64.into(), 0u64.into()]
                 ),
                 Transaction::from_sql_and_values(
                     DatabaseBackend::Postgres,
-                    r#"SELECT "todo"."id", "todo"."title", "todo"."description", "todo"."done", "todo"."created_at", "todo"."updated_at" FROM "todo" WHERE "todo"."title" LIKE $1 ORDER BY "todo"."updated_at" DESC LIMIT $2 OFFSET $3"#,
+                    r#"SELECT COUNT(*) AS num_items FROM (SELECT "todo"."id", "todo"."title", "todo"."description", "todo"."done", "todo"."created_at", "todo"."updated_at" FROM "todo" WHERE "todo"."title" LIKE $1 ORDER BY "todo"."created_at" DESC, "todo"."id" DESC) AS "sub_query""#,
+                    vec!["%Apple%".into()]
+                ),
+                Transaction::from_sql_and_values(
+                    DatabaseBackend::Postgres,
+                    r#"SELECT "todo"."id", "todo"."title", "todo"."description", "todo"."done", "todo"."created_at", "todo"."updated_at" FROM "todo" WHERE "todo"."title" LIKE $1 ORDER BY "todo"."created_at" DESC, "todo"."id" DESC LIMIT $2 OFFSET $3"#,
                     vec!["%Apple%".into(), 5u64.into(), 5u64.into()]
                 ),
             ]
@@ -280,10 +938,287 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_parse_sort() {
+        let sort = parse_sort("done,-updated_at").unwrap();
+        assert_eq!(sort.len(), 2);
+        assert!(matches!(sort[0].column, todo::Column::Done));
+        assert_eq!(sort[0].order, Order::Asc);
+        assert!(matches!(sort[1].column, todo::Column::UpdatedAt));
+        assert_eq!(sort[1].order, Order::Desc);
+
+        assert!(parse_sort("").unwrap().is_empty());
+        for sort in ["password", "-", "title,-title"] {
+            assert!(matches!(parse_sort(sort), Err(CustomError::BadRequestWithMsg { .. })));
+        }
+    }
+
+    #[test]
+    fn test_parse_fields() {
+        let fields = parse_fields("id, title,done,title").unwrap();
+        assert_eq!(fields.iter().map(|field| field.as_str()).collect::<Vec<_>>(), vec!["id", "title", "done"]);
+
+        for fields in ["", " , ", "id,password"] {
+            assert!(matches!(parse_fields(fields), Err(CustomError::BadRequestWithMsg { .. })));
+        }
+    }
+
+    #[async_std::test]
+    async fn test_find_todos_with_fields() -> Result<(), CustomError> {
+        let datetime = FixedOffset::east(0).ymd(2016, 11, 8).and_hms(0, 0, 0);
+        let row = |id: i32| {
+            BTreeMap::from([
+                ("id", Value::Int(Some(id))),
+                ("title", Value::String(Some(Box::new(format!("Todo {}", id))))),
+                ("created_at", datetime.into()),
+            ])
+        };
+        let db: Database = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results(vec![vec![num_items(3)]])
+            .append_query_results(vec![vec![row(3), row(2)]])
+            .into_connection()
+            .into();
+
+        let fields = parse_fields("title")?;
+        let page = find_todos::<TodoFields>(&db, &TodoFilter::default(), &[], &fields, Some(2), None).await?;
+        let items: Vec<_> = page.items.into_iter().map(|row| row.fields).collect();
+        assert_eq!(items, vec![serde_json::json!({ "title": "Todo 3" }), serde_json::json!({ "title": "Todo 2" })]);
+        assert_eq!(page.next_cursor, Some(Cursor::new(datetime, 2).encode()));
+
+        let select = r#"SELECT "todo"."title", "todo"."id", "todo"."created_at" FROM "todo" ORDER BY "todo"."created_at" DESC, "todo"."id" DESC"#;
+        assert_eq!(
+            db.conn.into_transaction_log(),
+            vec![
+                Transaction::from_sql_and_values(
+                    DatabaseBackend::Postgres,
+                    &format!(r#"SELECT COUNT(*) AS num_items FROM ({}) AS "sub_query""#, select),
+                    vec![]
+                ),
+                Transaction::from_sql_and_values(
+                    DatabaseBackend::Postgres,
+                    &format!("{} LIMIT $1 OFFSET $2", select),
+                    vec![2u64.into(), 0u64.into()]
+                ),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn test_find_todos_with_filters_and_sort() -> Result<(), CustomError> {
+        let datetime = FixedOffset::east(0).ymd(2016, 11, 8).and_hms(0, 0, 0);
+        let db: Database = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results(vec![vec![num_items(0)]])
+            .append_query_results(vec![Vec::<todo::Model>::new()])
+            .into_connection()
+            .into();
+
+        let filter = TodoFilter {
+            description: Some("milk".to_owned()),
+            done: Some(false),
+            created_from: Some(datetime),
+            updated_to: Some(datetime),
+            ..Default::default()
+        };
+        let sort = parse_sort("done,-created_at")?;
+        let page = find_todos::<todo::Model>(&db, &filter, &sort, &[], None, None).await?;
+        assert!(page.items.is_empty());
+        assert_eq!(page.next_cursor, None);
+
+        let filtered = r#"FROM "todo" WHERE "todo"."description" LIKE $1 AND "todo"."done" = $2 AND "todo"."created_at" >= $3 AND "todo"."updated_at" < $4 ORDER BY "todo"."done" ASC, "todo"."created_at" DESC, "todo"."id" DESC"#;
+        assert_eq!(
+            db.conn.into_transaction_log(),
+            vec![
+                Transaction::from_sql_and_values(
+                    DatabaseBackend::Postgres,
+                    &format!(r#"SELECT COUNT(*) AS num_items FROM (SELECT "todo"."id", "todo"."title", "todo"."description", "todo"."done", "todo"."created_at", "todo"."updated_at" {}) AS "sub_query""#, filtered),
+                    vec!["%milk%".into(), false.into(), datetime.into(), datetime.into()]
+                ),
+                Transaction::from_sql_and_values(
+                    DatabaseBackend::Postgres,
+                    &format!(r#"SELECT "todo"."id", "todo"."title", "todo"."description", "todo"."done", "todo"."created_at", "todo"."updated_at" {} LIMIT $5 OFFSET $6"#, filtered),
+                    vec!["%milk%".into(), false.into(), datetime.into(), datetime.into(), 10u64.into(), 0u64.into()]
+                ),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn test_search_todos() -> Result<(), CustomError> {
+        let datetime = FixedOffset::east(0).ymd(2016, 11, 8).and_hms(0, 0, 0);
+        let db: Database = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results(vec![vec![num_items(1)]])
+            .append_query_results(vec![vec![BTreeMap::from([
+                ("id", Value::Int(Some(1))),
+                ("title", Value::String(Some(Box::new("Pay rent".to_owned())))),
+                ("description", Value::String(Some(Box::new("Before the 5th".to_owned())))),
+                ("done", Value::Bool(Some(false))),
+                ("created_at", datetime.into()),
+                ("updated_at", datetime.into()),
+                ("rank", Value::Float(Some(0.6))),
+                ("title_highlight", Value::String(Some(Box::new("<mark>Pay</mark> rent".to_owned())))),
+                ("description_highlight", Value::String(Some(Box::new("Before the 5th".to_owned())))),
+            ])]])
+            .into_connection()
+            .into();
+
+        let page = search_todos(&db, "pay", &TodoFilter::default(), &[], None, None).await?;
+        assert_eq!(page.total_items, 1);
+        assert_eq!(
+            page.items,
+            vec![TodoSearchHit {
+                todo: todo::Model {
+                    id: 1,
+                    title: "Pay rent".to_owned(),
+                    description: "Before the 5th".to_owned(),
+                    done: false,
+                    created_at: datetime,
+                    updated_at: datetime,
+                },
+                rank: 0.6,
+                highlights: TodoHighlights {
+                    title: "<mark>Pay</mark> rent".to_owned(),
+                    description: "Before the 5th".to_owned(),
+                },
+            }]
+        );
+
+        let select = r#"SELECT "todo"."id", "todo"."title", "todo"."description", "todo"."done", "todo"."created_at", "todo"."updated_at", ts_rank("todo"."search", websearch_to_tsquery('english', $1)) AS "rank", ts_headline('english', replace(replace(replace(replace(replace("todo"."title", '&', '&amp;'), '<', '&lt;'), '>', '&gt;'), '"', '&quot;'), '''', '&#39;'), websearch_to_tsquery('english', $2), 'StartSel=<mark>, StopSel=</mark>, HighlightAll=true') AS "title_highlight", ts_headline('english', replace(replace(replace(replace(replace("todo"."description", '&', '&amp;'), '<', '&lt;'), '>', '&gt;'), '"', '&quot;'), '''', '&#39;'), websearch_to_tsquery('english', $3), 'StartSel=<mark>, StopSel=</mark>') AS "description_highlight" FROM "todo" WHERE "todo"."search" @@ websearch_to_tsquery('english', $4) ORDER BY "rank" DESC, "todo"."id" DESC"#;
+        assert_eq!(
+            db.conn.into_transaction_log(),
+            vec![
+                Transaction::from_sql_and_values(
+                    DatabaseBackend::Postgres,
+                    &format!(r#"SELECT COUNT(*) AS num_items FROM ({}) AS "sub_query""#, select),
+                    vec!["pay".into(), "pay".into(), "pay".into(), "pay".into()]
+                ),
+                Transaction::from_sql_and_values(
+                    DatabaseBackend::Postgres,
+                    &format!("{} LIMIT $5 OFFSET $6", select),
+                    vec!["pay".into(), "pay".into(), "pay".into(), "pay".into(), 10u64.into(), 0u64.into()]
+                ),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn test_suggest_titles() -> Result<(), CustomError> {
+        let db: Database = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results(vec![vec![BTreeMap::from([
+                ("id", Value::Int(Some(1))),
+                ("title", Value::String(Some(Box::new("Buy groceries".to_owned())))),
+                ("similarity", Value::Float(Some(0.8))),
+            ])]])
+            .into_connection()
+            .into();
+
+        assert_eq!(
+            suggest_titles(&db, "buy 100%", 5).await?,
+            vec![TodoSuggestion {
+                id: 1,
+                title: "Buy groceries".to_owned(),
+                similarity: 0.8,
+            }]
+        );
+        assert_eq!(
+            db.conn.into_transaction_log(),
+            vec![Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"SELECT "todo"."id", "todo"."title", word_similarity($1, "todo"."title") AS "similarity" FROM "todo" WHERE $2 <% "todo"."title" OR "todo"."title" ILIKE $3 ORDER BY "similarity" DESC, "todo"."title" ASC LIMIT $4"#,
+                vec!["buy 100%".into(), "buy 100%".into(), "buy 100\\%%".into(), 5u64.into()]
+            )]
+        );
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn test_find_todos_by_cursor() -> Result<(), CustomError> {
+        let datetime = FixedOffset::east(0).ymd(2016, 11, 8).and_hms(0, 0, 0);
+        let todo = |id: i32| todo::Model {
+            id,
+            title: format!("Todo {}", id),
+            description: "description".to_owned(),
+            done: false,
+            created_at: datetime,
+            updated_at: datetime,
+        };
+        let db: Database = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results(vec![vec![todo(4), todo(3), todo(2)]])
+            .append_query_results(vec![vec![todo(5)]])
+            .append_query_results(vec![vec![todo(6), todo(7)]])
+            .append_query_results(vec![vec![todo(5)]])
+            .append_query_results(vec![vec![todo(1)]])
+            .append_query_results(vec![Vec::<todo::Model>::new()])
+            .into_connection()
+            .into();
+
+        // after a cursor, the extra row means there is a next page, and a row listed before the page a previous one
+        let page = find_todos_by_cursor::<todo::Model>(&db, &TodoFilter::default(), &[], Some(2), CursorDirection::After(Cursor::new(datetime, 5))).await?;
+        assert_eq!(page.items, vec![todo(4), todo(3)]);
+        assert_eq!(page.next_cursor, Some(Cursor::new(datetime, 3).encode()));
+        assert_eq!(page.prev_cursor, Some(Cursor::new(datetime, 4).encode()));
+
+        // before a cursor, rows are read in reverse and put back in list order
+        let page = find_todos_by_cursor::<todo::Model>(&db, &TodoFilter::default(), &[], Some(2), CursorDirection::Before(Cursor::new(datetime, 5))).await?;
+        assert_eq!(page.items, vec![todo(7), todo(6)]);
+        assert_eq!(page.next_cursor, Some(Cursor::new(datetime, 6).encode()));
+        assert_eq!(page.prev_cursor, None);
+
+        // once the todos listed before a page are gone, it has no previous page
+        let page = find_todos_by_cursor::<todo::Model>(&db, &TodoFilter::default(), &[], Some(2), CursorDirection::After(Cursor::new(datetime, 2))).await?;
+        assert_eq!(page.items, vec![todo(1)]);
+        assert_eq!(page.next_cursor, None);
+        assert_eq!(page.prev_cursor, None);
+
+        assert_eq!(
+            db.conn.into_transaction_log(),
+            vec![
+                Transaction::from_sql_and_values(
+                    DatabaseBackend::Postgres,
+                    r#"SELECT "todo"."id", "todo"."title", "todo"."description", "todo"."done", "todo"."created_at", "todo"."updated_at" FROM "todo" WHERE "todo"."created_at" < $1 OR ("todo"."created_at" = $2 AND "todo"."id" < $3) ORDER BY "todo"."created_at" DESC, "todo"."id" DESC LIMIT $4"#,
+                    vec![datetime.into(), datetime.into(), 5i32.into(), 3u64.into()]
+                ),
+                Transaction::from_sql_and_values(
+                    DatabaseBackend::Postgres,
+                    r#"SELECT "todo"."id" FROM "todo" WHERE "todo"."created_at" > $1 OR ("todo"."created_at" = $2 AND "todo"."id" > $3) LIMIT $4"#,
+                    vec![datetime.into(), datetime.into(), 4i32.into(), 1u64.into()]
+                ),
+                Transaction::from_sql_and_values(
+                    DatabaseBackend::Postgres,
+                    r#"SELECT "todo"."id", "todo"."title", "todo"."description", "todo"."done", "todo"."created_at", "todo"."updated_at" FROM "todo" WHERE "todo"."created_at" > $1 OR ("todo"."created_at" = $2 AND "todo"."id" > $3) ORDER BY "todo"."created_at" ASC, "todo"."id" ASC LIMIT $4"#,
+                    vec![datetime.into(), datetime.into(), 5i32.into(), 3u64.into()]
+                ),
+                Transaction::from_sql_and_values(
+                    DatabaseBackend::Postgres,
+                    r#"SELECT "todo"."id" FROM "todo" WHERE "todo"."created_at" < $1 OR ("todo"."created_at" = $2 AND "todo"."id" < $3) LIMIT $4"#,
+                    vec![datetime.into(), datetime.into(), 6i32.into(), 1u64.into()]
+                ),
+                Transaction::from_sql_and_values(
+                    DatabaseBackend::Postgres,
+                    r#"SELECT "todo"."id", "todo"."title", "todo"."description", "todo"."done", "todo"."created_at", "todo"."updated_at" FROM "todo" WHERE "todo"."created_at" < $1 OR ("todo"."created_at" = $2 AND "todo"."id" < $3) ORDER BY "todo"."created_at" DESC, "todo"."id" DESC LIMIT $4"#,
+                    vec![datetime.into(), datetime.into(), 2i32.into(), 3u64.into()]
+                ),
+                Transaction::from_sql_and_values(
+                    DatabaseBackend::Postgres,
+                    r#"SELECT "todo"."id" FROM "todo" WHERE "todo"."created_at" > $1 OR ("todo"."created_at" = $2 AND "todo"."id" > $3) LIMIT $4"#,
+                    vec![datetime.into(), datetime.into(), 1i32.into(), 1u64.into()]
+                ),
+            ]
+        );
+
+        Ok(())
+    }
+
     #[async_std::test]
     async fn test_find_todo_by_id() -> Result<(), CustomError> {
         let datetime = FixedOffset::east(0).ymd(2016, 11, 08).and_hms(0, 0, 0);
-        let db = MockDatabase::new(DatabaseBackend::Postgres)
+        let db: Database = MockDatabase::new(DatabaseBackend::Postgres)
             .append_query_results(vec![
                 // First query result
                 vec![todo::Model {
@@ -297,7 +1232,8 @@ mod tests {
                 // Second query result
                 vec![],
             ])
-            .into_connection();
+            .into_connection()
+            .into();
 
         // testing find_todo_by_id with existing id
         assert_eq!(
@@ -315,7 +1251,7 @@ mod tests {
         // testing find_todo_by_id with non-existing id
         assert_eq!(
             find_todo_by_id(&db, 2).await.unwrap_err(),
-            CustomError::NotFound
+            CustomError::NotFoundWithCode { code: ErrorCode::TodoNotFound }
         );
 
         Ok(())
@@ -328,7 +1264,7 @@ mod tests {
         let done = false;
         let datetime = FixedOffset::east(0).ymd(2016, 11, 08).and_hms(0, 0, 0);
 
-        let db = MockDatabase::new(DatabaseBackend::Postgres)
+        let db: Database = MockDatabase::new(DatabaseBackend::Postgres)
             .append_query_results(vec![vec![todo::Model {
                 id: 15,
                 title: title.to_owned(),
@@ -341,15 +1277,26 @@ mod tests {
                 last_insert_id: 15,
                 rows_affected: 1,
             }])
-            .into_connection();
+            .into_connection()
+            .into();
 
-        insert_todo(&db, title, description, done).await?;
+        assert_eq!(
+            insert_todo(&db, title, description, done).await?,
+            todo::Model {
+                id: 15,
+                title: title.to_owned(),
+                description: description.to_owned(),
+                done,
+                created_at: datetime,
+                updated_at: datetime,
+            }
+        );
 
         assert_eq!(
-            db.into_transaction_log(),
+            db.conn.into_transaction_log(),
             vec![Transaction::from_sql_and_values(
                 DatabaseBackend::Postgres,
-                r#"INSERT INTO "todo" ("title", "description", "done") VALUES ($1, $2, $3) RETURNING "id""#,
+                r#"INSERT INTO "todo" ("title", "description", "done") VALUES ($1, $2, $3) RETURNING "id", "title", "description", "done", "created_at", "updated_at""#,
                 vec![title.into(), description.into(), done.into()]
             )]
         );
@@ -366,7 +1313,7 @@ mod tests {
         let done = false;
         let datetime = FixedOffset::east(0).ymd(2016, 11, 08).and_hms(0, 0, 0);
 
-        let db = MockDatabase::new(DatabaseBackend::Postgres)
+        let db: Database = MockDatabase::new(DatabaseBackend::Postgres)
             .append_query_results(vec![
                 // First query result
                 vec![todo::Model {
@@ -393,7 +1340,8 @@ mod tests {
                 last_insert_id: 15,
                 rows_affected: 1,
             }])
-            .into_connection();
+            .into_connection()
+            .into();
 
         // testing update_todo_by_id with existing id
         update_todo_by_id(
@@ -402,6 +1350,7 @@ mod tests {
             Some(title.to_owned()),
             Some(description.to_owned()),
             Some(done),
+            None,
         )
         .await?;
 
@@ -412,15 +1361,16 @@ mod tests {
                 2,
                 Some(title.to_owned()),
                 Some(description.to_owned()),
-                Some(done)
+                Some(done),
+                None,
             )
             .await
             .unwrap_err(),
-            CustomError::NotFound
+            CustomError::NotFoundWithCode { code: ErrorCode::TodoNotFound }
         );
 
         assert_eq!(
-            db.into_transaction_log()[..2],
+            db.conn.into_transaction_log()[..2],
             vec![
                 Transaction::from_sql_and_values(
                     DatabaseBackend::Postgres,
@@ -443,7 +1393,7 @@ mod tests {
         let id = 1;
         let datetime = FixedOffset::east(0).ymd(2016, 11, 08).and_hms(0, 0, 0);
 
-        let db = MockDatabase::new(DatabaseBackend::Postgres)
+        let db: Database = MockDatabase::new(DatabaseBackend::Postgres)
             .append_query_results(vec![
                 vec![todo::Model {
                     id,
@@ -460,16 +1410,17 @@ mod tests {
                 rows_affected: 1,
             }])
             .append_exec_results(vec![])
-            .into_connection();
+            .into_connection()
+            .into();
         // testing delete_todo_by_id with existing id
-        delete_todo_by_id(&db, id.try_into().unwrap()).await?;
+        delete_todo_by_id(&db, id.try_into().unwrap(), None).await?;
         // testing delete_todo_by_id with non-existing id
         assert_eq!(
-            delete_todo_by_id(&db, 2).await.unwrap_err(),
-            CustomError::NotFound
+            delete_todo_by_id(&db, 2, None).await.unwrap_err(),
+            CustomError::NotFoundWithCode { code: ErrorCode::TodoNotFound }
         );
         assert_eq!(
-            db.into_transaction_log()[1..2],
+            db.conn.into_transaction_log()[1..2],
             vec![Transaction::from_sql_and_values(
                 DatabaseBackend::Postgres,
                 r#"DELETE FROM "todo" WHERE "todo"."id" = $1"#,
@@ -480,10 +1431,72 @@ mod tests {
         Ok(())
     }
 
+    #[async_std::test]
+    async fn test_conditional_update_and_delete() -> Result<(), CustomError> {
+        let id = 1;
+        let title = "Test Title";
+        let datetime = FixedOffset::east(0).ymd(2016, 11, 8).and_hms(0, 0, 0);
+        let found = todo::Model {
+            id,
+            title: "Old Title".to_owned(),
+            description: "Test Description".to_owned(),
+            done: false,
+            created_at: datetime,
+            updated_at: datetime,
+        };
+        let current = IfMatch::Items(vec![etag(id, &datetime, Format::Json)]);
+        let stale = IfMatch::Items(vec![etag(id, &FixedOffset::east(0).ymd(2016, 11, 9).and_hms(0, 0, 0), Format::Json)]);
+
+        let db: Database = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results(vec![
+                vec![found.clone()],
+                vec![todo::Model { title: title.to_owned(), ..found.clone() }],
+                vec![found.clone()],
+                // the todo changes between the select and the update
+                vec![found.clone()],
+                vec![],
+                vec![found.clone()],
+            ])
+            .append_exec_results(vec![MockExecResult {
+                last_insert_id: 0,
+                rows_affected: 0,
+            }])
+            .into_connection()
+            .into();
+
+        update_todo_by_id(&db, 1, Some(title.to_owned()), None, None, Some(&current)).await?;
+        assert_eq!(
+            update_todo_by_id(&db, 1, Some(title.to_owned()), None, None, Some(&stale)).await.unwrap_err(),
+            CustomError::PreconditionFailed
+        );
+        assert_eq!(
+            update_todo_by_id(&db, 1, Some(title.to_owned()), None, None, Some(&current)).await.unwrap_err(),
+            CustomError::PreconditionFailed
+        );
+        assert_eq!(delete_todo_by_id(&db, 1, Some(&current)).await.unwrap_err(), CustomError::PreconditionFailed);
+
+        let update = Transaction::from_sql_and_values(
+            DatabaseBackend::Postgres,
+            r#"UPDATE "todo" SET "title" = $1 WHERE "todo"."id" = $2 AND "todo"."updated_at" = $3 RETURNING "id", "title", "description", "done", "created_at", "updated_at""#,
+            vec![title.into(), id.into(), datetime.into()],
+        );
+        let delete = Transaction::from_sql_and_values(
+            DatabaseBackend::Postgres,
+            r#"DELETE FROM "todo" WHERE "todo"."id" = $1 AND "todo"."updated_at" = $2"#,
+            vec![id.into(), datetime.into()],
+        );
+        let log = db.conn.into_transaction_log();
+        // The stale tag is rejected before updating, the other two updates are guarded by `updated_at`
+        assert_eq!(log.iter().filter(|transaction| **transaction == update).count(), 2);
+        assert!(log.contains(&delete));
+
+        Ok(())
+    }
+
     #[async_std::test]
     async fn test_bulk_delete_todos() -> Result<(), CustomError> {
         let datetime = FixedOffset::east(0).ymd(2016, 11, 08).and_hms(0, 0, 0);
-        let db = MockDatabase::new(DatabaseBackend::Postgres)
+        let db: Database = MockDatabase::new(DatabaseBackend::Postgres)
             .append_query_results(vec![
                 // First query result
                 vec![todo::Model {
@@ -525,7 +1538,8 @@ mod tests {
                 last_insert_id: 3,
                 rows_affected: 3,
             }])
-            .into_connection();
+            .into_connection()
+            .into();
         bulk_delete_todos_by_ids(&db, vec![1, 2, 3]).await?;
 
         // skipped assertion as there is no non-trivial way to test a transaction with many statements
@@ -0,0 +1,60 @@
+use chrono::{DateTime, FixedOffset, SecondsFormat};
+
+use crate::shared::errors::CustomError;
+use crate::shared::i18n::Message;
+
+/// An opaque position in a list ordered by a timestamp, with the id as a tie-breaker.
+/// Clients only ever see the encoded form, so the key can change without breaking them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cursor {
+    pub timestamp: DateTime<FixedOffset>,
+    pub id: i32,
+}
+
+impl Cursor {
+    pub fn new(timestamp: DateTime<FixedOffset>, id: i32) -> Self {
+        Self { timestamp, id }
+    }
+
+    /// Encodes the cursor as URL-safe base64.
+    pub fn encode(&self) -> String {
+        let key = format!("{}|{}", self.timestamp.to_rfc3339_opts(SecondsFormat::AutoSi, true), self.id);
+        base64::encode_config(key, base64::URL_SAFE_NO_PAD)
+    }
+
+    pub fn decode(encoded: &str) -> Result<Self, CustomError> {
+        let invalid = || CustomError::BadRequestWithMsg {
+            message: Message::new("invalid-cursor").arg("cursor", encoded),
+        };
+        let key = base64::decode_config(encoded, base64::URL_SAFE_NO_PAD).map_err(|_| invalid())?;
+        let key = String::from_utf8(key).map_err(|_| invalid())?;
+        let (timestamp, id) = key.split_once('|').ok_or_else(invalid)?;
+        Ok(Self {
+            timestamp: DateTime::parse_from_rfc3339(timestamp).map_err(|_| invalid())?,
+            id: id.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_cursor_round_trips() {
+        let timestamp = FixedOffset::east(0).ymd(2016, 11, 8).and_hms_micro(0, 0, 0, 123456);
+        let cursor = Cursor::new(timestamp, 42);
+
+        let encoded = cursor.encode();
+        assert!(!encoded.contains('|'));
+        assert_eq!(Cursor::decode(&encoded).unwrap(), cursor);
+    }
+
+    #[test]
+    fn test_invalid_cursor_is_a_bad_request() {
+        for encoded in ["not base64!", "bm8tc2VwYXJhdG9y", "eHw0Mg"] {
+            assert!(matches!(Cursor::decode(encoded), Err(CustomError::BadRequestWithMsg { .. })));
+        }
+    }
+}
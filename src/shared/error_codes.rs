@@ -0,0 +1,159 @@
+use serde::Serialize;
+
+/// Declares `ErrorCode` and `ErrorCode::ALL` from one list, so that a code cannot be left out of the catalog.
+macro_rules! error_codes {
+    ($($variant:ident,)*) => {
+        /// Stable, machine-readable error codes returned with every error.
+        /// ❗ Never remove or rename a code: clients branch on them.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum ErrorCode {
+            $($variant,)*
+        }
+
+        impl ErrorCode {
+            /// Every code, in declaration order.
+            pub const ALL: &'static [ErrorCode] = &[$(ErrorCode::$variant,)*];
+        }
+    };
+}
+
+error_codes! {
+    ValidationFailed,
+    BadRequest,
+    Unauthorized,
+    NotFound,
+    TodoNotFound,
+    MethodNotAllowed,
+    Conflict,
+    UniqueViolation,
+    TodoTitleTaken,
+    ForeignKeyViolation,
+    ConcurrentUpdate,
+    PreconditionFailed,
+    PreconditionRequired,
+    PayloadTooLarge,
+    UnsupportedMediaType,
+    NotAcceptable,
+    CheckViolation,
+    InternalError,
+    ServiceUnavailable,
+    DatabaseTimeout,
+}
+
+/// Unique constraints whose violation has a more specific code than `UNIQUE_VIOLATION`.
+const UNIQUE_CONSTRAINT_CODES: &[(&str, ErrorCode)] = &[("todo_title_key", ErrorCode::TodoTitleTaken)];
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::ValidationFailed => "VALIDATION_FAILED",
+            ErrorCode::BadRequest => "BAD_REQUEST",
+            ErrorCode::Unauthorized => "UNAUTHORIZED",
+            ErrorCode::NotFound => "NOT_FOUND",
+            ErrorCode::TodoNotFound => "TODO_NOT_FOUND",
+            ErrorCode::MethodNotAllowed => "METHOD_NOT_ALLOWED",
+            ErrorCode::Conflict => "CONFLICT",
+            ErrorCode::UniqueViolation => "UNIQUE_VIOLATION",
+            ErrorCode::TodoTitleTaken => "TODO_TITLE_TAKEN",
+            ErrorCode::ForeignKeyViolation => "FOREIGN_KEY_VIOLATION",
+            ErrorCode::ConcurrentUpdate => "CONCURRENT_UPDATE",
+            ErrorCode::PreconditionFailed => "PRECONDITION_FAILED",
+            ErrorCode::PreconditionRequired => "PRECONDITION_REQUIRED",
+            ErrorCode::PayloadTooLarge => "PAYLOAD_TOO_LARGE",
+            ErrorCode::UnsupportedMediaType => "UNSUPPORTED_MEDIA_TYPE",
+            ErrorCode::NotAcceptable => "NOT_ACCEPTABLE",
+            ErrorCode::CheckViolation => "CHECK_VIOLATION",
+            ErrorCode::InternalError => "INTERNAL_ERROR",
+            ErrorCode::ServiceUnavailable => "SERVICE_UNAVAILABLE",
+            ErrorCode::DatabaseTimeout => "DATABASE_TIMEOUT",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            ErrorCode::ValidationFailed => "One or more fields of the request body are invalid.",
+            ErrorCode::BadRequest => "The request is malformed, such as invalid JSON or a non-numeric id.",
+            ErrorCode::Unauthorized => "The request is missing valid credentials.",
+            ErrorCode::NotFound => "No resource exists at this path.",
+            ErrorCode::TodoNotFound => "No todo exists with the given id.",
+            ErrorCode::MethodNotAllowed => "The path exists but does not accept this method. See the Allow header.",
+            ErrorCode::Conflict => "The request conflicts with the current state of the resource.",
+            ErrorCode::UniqueViolation => "A resource with the same unique value already exists.",
+            ErrorCode::TodoTitleTaken => "A todo with the same title already exists.",
+            ErrorCode::ForeignKeyViolation => "The request references a resource that does not exist, or one that is still referenced.",
+            ErrorCode::ConcurrentUpdate => "The resource was changed concurrently. Retry the request.",
+            ErrorCode::PreconditionFailed => "The If-Match header does not match the current ETag of the resource.",
+            ErrorCode::PreconditionRequired => "The request must send the ETag of the resource in an If-Match header.",
+            ErrorCode::PayloadTooLarge => "The request body exceeds the size limit.",
+            ErrorCode::UnsupportedMediaType => "The request body has an unsupported Content-Type.",
+            ErrorCode::NotAcceptable => "The response cannot be written in any format the Accept header allows.",
+            ErrorCode::CheckViolation => "A value violates a database check constraint.",
+            ErrorCode::InternalError => "An unexpected server error occurred.",
+            ErrorCode::ServiceUnavailable => "The service or its database is temporarily unavailable. Retry later.",
+            ErrorCode::DatabaseTimeout => "The database did not respond in time. Retry later.",
+        }
+    }
+
+    /// Returns the code for a violated unique constraint.
+    pub fn for_unique_constraint(constraint: &str) -> ErrorCode {
+        UNIQUE_CONSTRAINT_CODES
+            .iter()
+            .find(|(name, _)| *name == constraint)
+            .map_or(ErrorCode::UniqueViolation, |(_, code)| *code)
+    }
+}
+
+impl Serialize for ErrorCode {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codes_are_stable() {
+        // ❗ If this fails, a code was removed or renamed. Clients depend on these: add new codes, never remove them.
+        let codes: Vec<&str> = ErrorCode::ALL.iter().map(|code| code.as_str()).collect();
+        for expected in [
+            "VALIDATION_FAILED",
+            "BAD_REQUEST",
+            "UNAUTHORIZED",
+            "NOT_FOUND",
+            "TODO_NOT_FOUND",
+            "METHOD_NOT_ALLOWED",
+            "CONFLICT",
+            "UNIQUE_VIOLATION",
+            "TODO_TITLE_TAKEN",
+            "FOREIGN_KEY_VIOLATION",
+            "CONCURRENT_UPDATE",
+            "PRECONDITION_FAILED",
+            "PRECONDITION_REQUIRED",
+            "PAYLOAD_TOO_LARGE",
+            "UNSUPPORTED_MEDIA_TYPE",
+            "NOT_ACCEPTABLE",
+            "CHECK_VIOLATION",
+            "INTERNAL_ERROR",
+            "SERVICE_UNAVAILABLE",
+            "DATABASE_TIMEOUT",
+        ] {
+            assert!(codes.contains(&expected), "error code {} was removed", expected);
+        }
+    }
+
+    #[test]
+    fn test_codes_are_unique() {
+        let mut codes: Vec<&str> = ErrorCode::ALL.iter().map(|code| code.as_str()).collect();
+        codes.sort_unstable();
+        codes.dedup();
+        assert_eq!(codes.len(), ErrorCode::ALL.len());
+    }
+
+    #[test]
+    fn test_for_unique_constraint() {
+        assert_eq!(ErrorCode::for_unique_constraint("todo_title_key"), ErrorCode::TodoTitleTaken);
+        assert_eq!(ErrorCode::for_unique_constraint("other_key"), ErrorCode::UniqueViolation);
+    }
+}
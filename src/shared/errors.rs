@@ -1,20 +1,212 @@
-use actix_web::{error::ResponseError, http::StatusCode, HttpResponse};
+use actix_web::{
+    error::{JsonPayloadError, PathError, QueryPayloadError, ResponseError},
+    http::{header, StatusCode},
+    HttpResponse,
+};
 use derive_more::{Display, Error};
+use fluent_bundle::FluentArgs;
+use sea_orm::DbErr;
 use serde::Serialize;
-use validator::ValidationErrors;
+use serde_json::{Map, Value};
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::fmt;
+use crate::shared::error_codes::ErrorCode;
+use crate::shared::i18n::{localizer, Message, DEFAULT_LOCALE};
+use crate::shared::responses::json_response;
+use validator::{ValidationErrors, ValidationErrorsKind};
+
+/// Formats an error followed by each of its sources, outermost first.
+pub fn error_chain(e: &dyn std::error::Error) -> String {
+    let mut chain = e.to_string();
+    let mut source = e.source();
+    while let Some(cause) = source {
+        chain.push_str(": ");
+        chain.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    chain
+}
 
 #[derive(Serialize)]
 struct FormattedErrorResponse {
     status_code: u16,
+    code: ErrorCode,
     error: String,
     message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    conflict: Option<ConflictDetails>,
+}
+
+/// Identifies what a write collided with, so that clients can point at the existing resource.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ConflictDetails {
+    pub field: String,
+    pub constraint: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub existing_id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub existing_url: Option<String>,
 }
 
 #[derive(Serialize)]
 struct FormattedValidationErrorResponse {
     status_code: u16,
+    code: ErrorCode,
     error: String,
-    message: ValidationErrors,
+    message: String,
+    errors: Vec<FieldError>,
+}
+
+/// A single validation failure. `field` is the path of the offending field, such as `title`, `owner.name` or `tags[0].name`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct FieldError {
+    pub field: String,
+    pub code: String,
+    pub message: String,
+    pub params: Map<String, Value>,
+}
+
+/// Flattens the validator's nested errors into one entry per failure, ordered by field path with list items in
+/// index order. The offending value is left out of `params` so that client input is never echoed back.
+pub fn flatten_validation_errors(errors: &ValidationErrors) -> Vec<FieldError> {
+    let mut flattened = vec![];
+    collect_field_errors("", errors, &mut flattened);
+    flattened.sort_by(|a, b| {
+        path_segments(&a.field)
+            .cmp(&path_segments(&b.field))
+            .then_with(|| a.code.cmp(&b.code))
+    });
+    flattened
+}
+
+/// A segment of a field path: the name of a field, or the index of a list item.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum PathSegment<'a> {
+    Field(&'a str),
+    Index(usize),
+}
+
+/// Splits a path such as `tags[10].name` into its segments, so that `tags[2]` sorts before `tags[10]`.
+fn path_segments(path: &str) -> Vec<PathSegment<'_>> {
+    path.split(['.', '['])
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| match segment.strip_suffix(']').and_then(|index| index.parse().ok()) {
+            Some(index) => PathSegment::Index(index),
+            None => PathSegment::Field(segment),
+        })
+        .collect()
+}
+
+fn collect_field_errors(prefix: &str, errors: &ValidationErrors, flattened: &mut Vec<FieldError>) {
+    let path = |field: &str| {
+        if prefix.is_empty() {
+            field.to_owned()
+        } else {
+            format!("{}.{}", prefix, field)
+        }
+    };
+    for (&field, kind) in errors.errors() {
+        match kind {
+            ValidationErrorsKind::Field(field_errors) => {
+                flattened.extend(field_errors.iter().map(|error| FieldError {
+                    field: path(field),
+                    code: error.code.to_string(),
+                    message: error.message.as_deref().unwrap_or(&error.code).to_owned(),
+                    params: error
+                        .params
+                        .iter()
+                        .filter(|(name, _)| name.as_ref() != "value")
+                        .map(|(name, value)| (name.to_string(), value.to_owned()))
+                        .collect(),
+                }))
+            }
+            ValidationErrorsKind::Struct(nested) => collect_field_errors(&path(field), nested, flattened),
+            ValidationErrorsKind::List(items) => {
+                for (index, nested) in items {
+                    collect_field_errors(&format!("{}[{}]", path(field), index), nested, flattened);
+                }
+            }
+        }
+    }
+}
+
+/// The media type of problem details responses.
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+/// An RFC 7807 problem details object.
+#[derive(Serialize)]
+pub struct ProblemDetails {
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    pub code: ErrorCode,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub errors: Option<Vec<FieldError>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conflict: Option<ConflictDetails>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+/// The underlying cause of an unexpected error, kept for logs and error reports but never shown to clients.
+/// Sources compare equal when their messages do, so that `CustomError` stays comparable in tests.
+pub struct ErrorSource {
+    error: Box<dyn std::error::Error + Send + Sync>,
+    /// Where the error was wrapped, if backtraces are enabled with `RUST_BACKTRACE` or `RUST_LIB_BACKTRACE`.
+    backtrace: Backtrace,
+}
+
+impl ErrorSource {
+    pub fn new(e: impl std::error::Error + Send + Sync + 'static) -> Self {
+        Self::from(Box::new(e) as Box<dyn std::error::Error + Send + Sync>)
+    }
+
+    /// Wraps an error that is only known by its message.
+    pub fn from_message(message: impl Into<String>) -> Self {
+        let message: String = message.into();
+        Self::from(Box::<dyn std::error::Error + Send + Sync>::from(message))
+    }
+
+    pub fn backtrace(&self) -> Option<&Backtrace> {
+        match self.backtrace.status() {
+            BacktraceStatus::Captured => Some(&self.backtrace),
+            _ => None,
+        }
+    }
+}
+
+impl From<Box<dyn std::error::Error + Send + Sync>> for ErrorSource {
+    fn from(error: Box<dyn std::error::Error + Send + Sync>) -> Self {
+        Self { error, backtrace: Backtrace::capture() }
+    }
+}
+
+impl fmt::Debug for ErrorSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.error, f)
+    }
+}
+
+impl fmt::Display for ErrorSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.error, f)
+    }
+}
+
+impl std::error::Error for ErrorSource {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.error.source()
+    }
+}
+
+impl PartialEq for ErrorSource {
+    fn eq(&self, other: &Self) -> bool {
+        self.error.to_string() == other.error.to_string()
+    }
 }
 
 #[derive(Debug, Display, Error, PartialEq)]
@@ -23,31 +215,196 @@ pub enum CustomError {
     ValidationError { e: ValidationErrors },
     #[display(fmt = "Internal server error. Please try again later.")]
     ServerError,
-    #[display(fmt = "Bad request")]
-    BadRequestWithMsg { message: String },
+    #[display(fmt = "Internal server error. Please try again later.")]
+    Internal { source: ErrorSource },
+    #[display(fmt = "{}", message)]
+    BadRequestWithMsg { message: Message },
     #[display(fmt = "Bad request")]
     BadRequest,
-    #[display(fmt = "Not found")]
+    #[display(fmt = "{}", message)]
     NotFoundWithMsg { message: String },
     #[display(fmt = "Not found")]
     NotFound,
+    #[display(fmt = "Not found")]
+    NotFoundWithCode { code: ErrorCode },
     #[display(fmt = "Conflict")]
     Conflict,
     #[display(fmt = "Unauthorized")]
     Unauthorized,
+    #[display(fmt = "Unique constraint {} violated", constraint)]
+    UniqueViolation { constraint: String },
+    #[display(fmt = "A resource with the same unique value already exists")]
+    Duplicate { conflict: ConflictDetails },
+    #[display(fmt = "Foreign key constraint {} violated", constraint)]
+    ForeignKeyViolation { constraint: String },
+    #[display(fmt = "Check constraint {} violated", constraint)]
+    CheckViolation { constraint: String },
+    #[display(fmt = "Conflict with a concurrent update. Please try again.")]
+    SerializationFailure,
+    #[display(fmt = "The resource was changed since it was read. Fetch it again and retry.")]
+    PreconditionFailed,
+    #[display(fmt = "This request must be conditional. Send the ETag of the resource in If-Match.")]
+    PreconditionRequired,
+    #[display(fmt = "The request timed out. Please try again later.")]
+    Timeout { source: ErrorSource },
+    #[display(fmt = "Service unavailable. Please try again later.")]
+    Unavailable { source: ErrorSource },
+    #[display(fmt = "{}", message)]
+    UnsupportedMediaType { message: Message },
+    #[display(fmt = "{}", message)]
+    NotAcceptable { message: Message },
+    #[display(fmt = "{}", message)]
+    PayloadTooLarge { message: Message },
+    #[display(fmt = "Method not allowed")]
+    MethodNotAllowed { allowed: Vec<String> },
 }
 
 impl CustomError {
-    fn message(&self) -> String {
+    /// Returns the stable code identifying this error in the catalog.
+    pub fn code(&self) -> ErrorCode {
         match self {
-            CustomError::ServerError => "Internal Server Error".to_owned(),
-            CustomError::BadRequest => "Bad Request".to_owned(),
-            CustomError::BadRequestWithMsg { .. } => "Bad Request".to_owned(),
-            CustomError::NotFound => "Not Found".to_owned(),
-            CustomError::NotFoundWithMsg { .. } => "Not Found".to_owned(),
-            CustomError::Conflict => "Conflict".to_owned(),
-            CustomError::Unauthorized => "Unauthorized".to_owned(),
-            _ => "".to_owned(),
+            CustomError::ValidationError { .. } => ErrorCode::ValidationFailed,
+            CustomError::ServerError => ErrorCode::InternalError,
+            CustomError::Internal { .. } => ErrorCode::InternalError,
+            CustomError::BadRequestWithMsg { .. } => ErrorCode::BadRequest,
+            CustomError::BadRequest => ErrorCode::BadRequest,
+            CustomError::NotFoundWithMsg { .. } => ErrorCode::NotFound,
+            CustomError::NotFound => ErrorCode::NotFound,
+            CustomError::NotFoundWithCode { code } => *code,
+            CustomError::Conflict => ErrorCode::Conflict,
+            CustomError::Unauthorized => ErrorCode::Unauthorized,
+            CustomError::UniqueViolation { constraint } => ErrorCode::for_unique_constraint(constraint),
+            CustomError::Duplicate { conflict } => ErrorCode::for_unique_constraint(&conflict.constraint),
+            CustomError::ForeignKeyViolation { .. } => ErrorCode::ForeignKeyViolation,
+            CustomError::CheckViolation { .. } => ErrorCode::CheckViolation,
+            CustomError::SerializationFailure => ErrorCode::ConcurrentUpdate,
+            CustomError::PreconditionFailed => ErrorCode::PreconditionFailed,
+            CustomError::PreconditionRequired => ErrorCode::PreconditionRequired,
+            CustomError::Timeout { .. } => ErrorCode::DatabaseTimeout,
+            CustomError::Unavailable { .. } => ErrorCode::ServiceUnavailable,
+            CustomError::UnsupportedMediaType { .. } => ErrorCode::UnsupportedMediaType,
+            CustomError::NotAcceptable { .. } => ErrorCode::NotAcceptable,
+            CustomError::PayloadTooLarge { .. } => ErrorCode::PayloadTooLarge,
+            CustomError::MethodNotAllowed { .. } => ErrorCode::MethodNotAllowed,
+        }
+    }
+
+    /// Where the cause of an unexpected error was caught, if a backtrace was captured.
+    pub fn backtrace(&self) -> Option<&Backtrace> {
+        match self {
+            CustomError::Internal { source } | CustomError::Timeout { source } | CustomError::Unavailable { source } => {
+                source.backtrace()
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether this error was raised by the database, whatever status it is answered with.
+    pub fn is_database_error(&self) -> bool {
+        matches!(
+            self,
+            CustomError::UniqueViolation { .. }
+                | CustomError::Duplicate { .. }
+                | CustomError::ForeignKeyViolation { .. }
+                | CustomError::CheckViolation { .. }
+                | CustomError::SerializationFailure
+                | CustomError::Timeout { .. }
+                | CustomError::Unavailable { .. }
+        )
+    }
+
+    /// Returns the localized title of this error, which depends on its status code only.
+    fn title(&self, locale: &str) -> String {
+        let status_code = self.status_code();
+        localizer()
+            .message(locale, &format!("status-{}", status_code.as_u16()), None)
+            .unwrap_or_else(|| status_code.canonical_reason().unwrap_or_default().to_owned())
+    }
+
+    /// Returns the localized message of this error.
+    fn detail(&self, locale: &str) -> String {
+        let constraint = match self {
+            CustomError::BadRequestWithMsg { message }
+            | CustomError::UnsupportedMediaType { message }
+            | CustomError::NotAcceptable { message }
+            | CustomError::PayloadTooLarge { message } => return message.localize(locale),
+            CustomError::NotFoundWithMsg { message } => return message.to_owned(),
+            CustomError::UniqueViolation { constraint }
+            | CustomError::ForeignKeyViolation { constraint }
+            | CustomError::CheckViolation { constraint } => constraint.as_str(),
+            CustomError::Duplicate { conflict } => conflict.constraint.as_str(),
+            _ => "",
+        };
+        let mut args = FluentArgs::new();
+        args.set("constraint", constraint.to_owned());
+        localizer()
+            .message(locale, self.code().as_str(), Some(&args))
+            .unwrap_or_else(|| self.to_string())
+    }
+
+    fn conflict(&self) -> Option<ConflictDetails> {
+        match self {
+            CustomError::Duplicate { conflict } => Some(conflict.to_owned()),
+            _ => None,
+        }
+    }
+
+    /// Returns the problem details for this error in the given locale,
+    /// without the request-specific `instance` and `request_id` members.
+    pub fn problem_details(&self, locale: &str) -> ProblemDetails {
+        let errors = match self {
+            CustomError::ValidationError { e } => Some(flatten_validation_errors(
+                &localizer().localize_validation_errors(locale, e),
+            )),
+            _ => None,
+        };
+        ProblemDetails {
+            problem_type: format!("/error_codes#{}", self.code().as_str()),
+            title: self.title(locale),
+            status: self.status_code().as_u16(),
+            detail: self.detail(locale),
+            code: self.code(),
+            instance: None,
+            errors,
+            conflict: self.conflict(),
+            request_id: None,
+        }
+    }
+
+    /// Renders this error in the given locale, in the default (non problem details) format.
+    pub fn render(&self, locale: &str) -> HttpResponse {
+        let mut res = if let CustomError::ValidationError { e } = &self {
+            let response = FormattedValidationErrorResponse {
+                status_code: self.status_code().as_u16(),
+                code: self.code(),
+                error: self.title(locale),
+                message: self.detail(locale),
+                errors: flatten_validation_errors(&localizer().localize_validation_errors(locale, e)),
+            };
+            json_response(self.status_code(), &response)
+        } else {
+            let response = FormattedErrorResponse {
+                status_code: self.status_code().as_u16(),
+                code: self.code(),
+                error: self.title(locale),
+                message: self.detail(locale),
+                conflict: self.conflict(),
+            };
+            json_response(self.status_code(), &response)
+        };
+        self.insert_headers(&mut res, locale);
+        res
+    }
+
+    /// Adds the `Allow` header of a 405 and the `Content-Language` of the rendered message.
+    fn insert_headers(&self, res: &mut HttpResponse, locale: &str) {
+        if let CustomError::MethodNotAllowed { allowed } = &self {
+            if let Ok(allow) = header::HeaderValue::from_str(&allowed.join(", ")) {
+                res.headers_mut().insert(header::ALLOW, allow);
+            }
+        }
+        if let Ok(language) = header::HeaderValue::from_str(locale) {
+            res.headers_mut().insert(header::CONTENT_LANGUAGE, language);
         }
     }
 }
@@ -56,31 +413,300 @@ impl ResponseError for CustomError {
     fn status_code(&self) -> StatusCode {
         match *self {
             CustomError::ServerError => StatusCode::INTERNAL_SERVER_ERROR,
+            CustomError::Internal { .. } => StatusCode::INTERNAL_SERVER_ERROR,
             CustomError::ValidationError { .. } => StatusCode::BAD_REQUEST,
             CustomError::BadRequest => StatusCode::BAD_REQUEST,
             CustomError::BadRequestWithMsg { .. } => StatusCode::BAD_REQUEST,
             CustomError::NotFound => StatusCode::NOT_FOUND,
             CustomError::NotFoundWithMsg { .. } => StatusCode::NOT_FOUND,
+            CustomError::NotFoundWithCode { .. } => StatusCode::NOT_FOUND,
             CustomError::Conflict => StatusCode::CONFLICT,
             CustomError::Unauthorized => StatusCode::UNAUTHORIZED,
+            CustomError::UniqueViolation { .. } => StatusCode::CONFLICT,
+            CustomError::Duplicate { .. } => StatusCode::CONFLICT,
+            CustomError::ForeignKeyViolation { .. } => StatusCode::CONFLICT,
+            CustomError::CheckViolation { .. } => StatusCode::BAD_REQUEST,
+            CustomError::SerializationFailure => StatusCode::CONFLICT,
+            CustomError::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
+            CustomError::PreconditionRequired => StatusCode::PRECONDITION_REQUIRED,
+            CustomError::Timeout { .. } => StatusCode::GATEWAY_TIMEOUT,
+            CustomError::Unavailable { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            CustomError::UnsupportedMediaType { .. } => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            CustomError::NotAcceptable { .. } => StatusCode::NOT_ACCEPTABLE,
+            CustomError::PayloadTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            CustomError::MethodNotAllowed { .. } => StatusCode::METHOD_NOT_ALLOWED,
         }
     }
 
     fn error_response(&self) -> HttpResponse {
-        if let CustomError::ValidationError { e } = &self {
-            let response = FormattedValidationErrorResponse {
-                status_code: self.status_code().as_u16(),
-                error: self.message(),
-                message: e.to_owned(),
-            };
-            HttpResponse::build(self.status_code()).json(response)
+        self.render(DEFAULT_LOCALE)
+    }
+}
+
+/// Extracts the constraint name from a Postgres error message such as
+/// `duplicate key value violates unique constraint "todo_title_key"`.
+fn constraint_name(message: &str) -> String {
+    message
+        .split("constraint \"")
+        .nth(1)
+        .and_then(|rest| rest.split('"').next())
+        .unwrap_or_default()
+        .to_owned()
+}
+
+/// Translates a database error into the matching `CustomError`.
+///
+/// SeaORM 0.9 flattens sqlx errors into their message and drops the SQLSTATE, so errors are classified by matching
+/// the English text of the server's message: unique, foreign key and check violations, serialization failures and
+/// deadlocks, statement or lock timeouts, and connection or pool failures as unavailable. The server translates its
+/// messages into the language of its `lc_messages` setting, so on a server that does not use English, violations,
+/// serialization failures and timeouts are not recognized and fall through to `Internal`; only the failures whose
+/// messages sqlx writes itself, such as pool timeouts, are still told apart. Server-side failures keep the original
+/// error as their source.
+impl From<DbErr> for CustomError {
+    fn from(e: DbErr) -> Self {
+        let message = match &e {
+            DbErr::Conn(message) | DbErr::Exec(message) | DbErr::Query(message) => message,
+            _ => return CustomError::Internal { source: ErrorSource::new(e) },
+        };
+
+        if message.contains("violates unique constraint") {
+            CustomError::UniqueViolation { constraint: constraint_name(message) }
+        } else if message.contains("violates foreign key constraint") {
+            CustomError::ForeignKeyViolation { constraint: constraint_name(message) }
+        } else if message.contains("violates check constraint") {
+            CustomError::CheckViolation { constraint: constraint_name(message) }
+        } else if message.contains("could not serialize access") || message.contains("deadlock detected") {
+            CustomError::SerializationFailure
+        } else if message.contains("canceling statement due to")
+            || message.contains("pool timed out")
+        {
+            CustomError::Timeout { source: ErrorSource::new(e) }
+        } else if matches!(e, DbErr::Conn(_))
+            || message.contains("error communicating with database")
+            || message.contains("closed pool")
+            || message.contains("too many clients")
+            || message.contains("terminating connection")
+        {
+            CustomError::Unavailable { source: ErrorSource::new(e) }
         } else {
-            let response = FormattedErrorResponse {
-                status_code: self.status_code().as_u16(),
-                error: self.message(),
-                message: self.to_string(),
-            };
-            HttpResponse::build(self.status_code()).json(response)
+            CustomError::Internal { source: ErrorSource::new(e) }
+        }
+    }
+}
+
+/// Translates a JSON body extractor failure, pointing at the line and column of malformed or invalid JSON.
+impl From<JsonPayloadError> for CustomError {
+    fn from(e: JsonPayloadError) -> Self {
+        match e {
+            JsonPayloadError::Deserialize(e) if e.is_data() => CustomError::BadRequestWithMsg {
+                message: Message::new("invalid-request-body").arg("error", e),
+            },
+            JsonPayloadError::Deserialize(e) => CustomError::BadRequestWithMsg {
+                message: Message::new("malformed-json").arg("error", e),
+            },
+            JsonPayloadError::ContentType => CustomError::UnsupportedMediaType {
+                message: Message::new("json-content-type-required"),
+            },
+            JsonPayloadError::OverflowKnownLength { length, limit } => CustomError::PayloadTooLarge {
+                message: Message::new("body-of-length-too-large").arg("length", length).arg("limit", limit),
+            },
+            JsonPayloadError::Overflow { limit } => CustomError::PayloadTooLarge {
+                message: Message::new("body-too-large").arg("limit", limit),
+            },
+            e => CustomError::BadRequestWithMsg {
+                message: Message::new("invalid-request-body").arg("error", e),
+            },
+        }
+    }
+}
+
+/// Translates a path extractor failure, such as a non-numeric id.
+impl From<PathError> for CustomError {
+    fn from(e: PathError) -> Self {
+        CustomError::BadRequestWithMsg {
+            message: Message::new("invalid-path-parameter").arg("error", e),
+        }
+    }
+}
+
+/// Translates a query string extractor failure, such as a non-numeric page.
+impl From<QueryPayloadError> for CustomError {
+    fn from(e: QueryPayloadError) -> Self {
+        CustomError::BadRequestWithMsg {
+            message: Message::new("invalid-query-parameter").arg("error", e),
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use validator::Validate;
+
+    #[test]
+    fn test_flatten_validation_errors() {
+        #[derive(Validate)]
+        struct Tag {
+            #[validate(length(min = 1, max = 30))]
+            name: String,
+        }
+
+        #[derive(Validate)]
+        struct Dto {
+            #[validate(length(min = 1, max = 30))]
+            title: String,
+            #[validate]
+            owner: Tag,
+            #[validate]
+            tags: Vec<Tag>,
+        }
+
+        let dto = Dto {
+            title: "".to_owned(),
+            owner: Tag { name: "".to_owned() },
+            tags: vec![Tag { name: "ok".to_owned() }, Tag { name: "".to_owned() }],
+        };
+        let errors = localizer().localize_validation_errors(DEFAULT_LOCALE, &dto.validate().unwrap_err());
+        let flattened = flatten_validation_errors(&errors);
+
+        let fields: Vec<&str> = flattened.iter().map(|error| error.field.as_str()).collect();
+        assert_eq!(fields, vec!["owner.name", "tags[1].name", "title"]);
+        let title = &flattened[2];
+        assert_eq!(title.code, "length");
+        assert_eq!(title.message, "Must be between 1 and 30 characters long.");
+        assert_eq!(serde_json::to_value(&title.params).unwrap(), serde_json::json!({"min": 1, "max": 30}));
+    }
+
+    #[test]
+    fn test_flatten_validation_errors_orders_list_items_by_index() {
+        #[derive(Validate)]
+        struct Tag {
+            #[validate(length(min = 1))]
+            name: String,
+        }
+
+        #[derive(Validate)]
+        struct Dto {
+            #[validate]
+            tags: Vec<Tag>,
+        }
+
+        let dto = Dto {
+            tags: (0..11)
+                .map(|index| Tag { name: if index % 4 == 2 { "".to_owned() } else { "ok".to_owned() } })
+                .collect(),
+        };
+        let flattened = flatten_validation_errors(&dto.validate().unwrap_err());
+
+        let fields: Vec<&str> = flattened.iter().map(|error| error.field.as_str()).collect();
+        assert_eq!(fields, vec!["tags[2].name", "tags[6].name", "tags[10].name"]);
+    }
+
+    fn source(e: DbErr) -> ErrorSource {
+        ErrorSource::new(e)
+    }
+
+    #[test]
+    fn test_from_db_err() {
+        let cases = vec![
+            (
+                DbErr::Query(r#"error returned from database: duplicate key value violates unique constraint "todo_title_key""#.to_owned()),
+                CustomError::UniqueViolation { constraint: "todo_title_key".to_owned() },
+            ),
+            (
+                DbErr::Exec(r#"error returned from database: update or delete on table "todo" violates foreign key constraint "tag_todo_id_fkey" on table "tag""#.to_owned()),
+                CustomError::ForeignKeyViolation { constraint: "tag_todo_id_fkey".to_owned() },
+            ),
+            (
+                DbErr::Query(r#"error returned from database: new row for relation "todo" violates check constraint "todo_title_check""#.to_owned()),
+                CustomError::CheckViolation { constraint: "todo_title_check".to_owned() },
+            ),
+            (
+                DbErr::Query("error returned from database: could not serialize access due to concurrent update".to_owned()),
+                CustomError::SerializationFailure,
+            ),
+            (
+                DbErr::Query("error returned from database: canceling statement due to statement timeout".to_owned()),
+                CustomError::Timeout { source: source(DbErr::Query("error returned from database: canceling statement due to statement timeout".to_owned())) },
+            ),
+            (
+                DbErr::Conn("pool timed out while waiting for an open connection".to_owned()),
+                CustomError::Timeout { source: source(DbErr::Conn("pool timed out while waiting for an open connection".to_owned())) },
+            ),
+            (
+                DbErr::Conn("error communicating with database: Connection refused (os error 111)".to_owned()),
+                CustomError::Unavailable { source: source(DbErr::Conn("error communicating with database: Connection refused (os error 111)".to_owned())) },
+            ),
+            (
+                DbErr::Query(r#"error returned from database: syntax error at or near "SELEC""#.to_owned()),
+                CustomError::Internal { source: source(DbErr::Query(r#"error returned from database: syntax error at or near "SELEC""#.to_owned())) },
+            ),
+            (
+                DbErr::RecordNotFound("todo".to_owned()),
+                CustomError::Internal { source: source(DbErr::RecordNotFound("todo".to_owned())) },
+            ),
+        ];
+
+        for (e, expected) in cases {
+            let e = CustomError::from(e);
+            assert_eq!(e.is_database_error(), !matches!(e, CustomError::Internal { .. }));
+            assert_eq!(e, expected);
+        }
+    }
+
+    #[test]
+    fn test_unexpected_errors_report_their_backtrace() {
+        use crate::core::error_reporting::ErrorEvent;
+
+        let internal = |backtrace| CustomError::Internal {
+            source: ErrorSource { backtrace, ..source(DbErr::Query("boom".to_owned())) },
+        };
+        let event = ErrorEvent::from_error(&internal(Backtrace::force_capture()));
+        assert!(event.extra["backtrace"].as_str().is_some_and(|backtrace| !backtrace.is_empty()));
+        let event = ErrorEvent::from_error(&internal(Backtrace::disabled()));
+        assert!(!event.extra.contains_key("backtrace"));
+    }
+
+    #[test]
+    fn test_internal_errors_keep_their_source() {
+        let e = CustomError::from(DbErr::Query("error returned from database: relation \"todo\" does not exist".to_owned()));
+
+        assert_eq!(e.to_string(), "Internal server error. Please try again later.");
+        assert_eq!(
+            error_chain(&e),
+            "Internal server error. Please try again later.: Query Error: error returned from database: relation \"todo\" does not exist"
+        );
+        assert_eq!(e.render(DEFAULT_LOCALE).status().as_u16(), 500);
+    }
+
+    #[test]
+    fn test_from_json_payload_error() {
+        #[derive(serde::Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Dto {
+            title: String,
+        }
+
+        let e = serde_json::from_str::<Dto>("{\"title\": ").unwrap_err();
+        match CustomError::from(JsonPayloadError::Deserialize(e)) {
+            CustomError::BadRequestWithMsg { message } => {
+                assert!(message.to_string().starts_with("Malformed JSON: EOF while parsing a value at line 1 column"))
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+
+        let e = serde_json::from_str::<Dto>("{}").unwrap_err();
+        let e = CustomError::from(JsonPayloadError::Deserialize(e));
+        assert_eq!(
+            e,
+            CustomError::BadRequestWithMsg {
+                message: Message::new("invalid-request-body").arg("error", "missing field `title` at line 1 column 2")
+            }
+        );
+        assert_eq!(
+            e.problem_details("es").detail,
+            "Cuerpo de la solicitud no válido: missing field `title` at line 1 column 2"
+        );
+    }
+}
@@ -0,0 +1,160 @@
+use actix_web::http::header::{self, EntityTag, Header, HeaderValue, IfMatch, IfNoneMatch};
+use actix_web::{HttpRequest, HttpResponse};
+use chrono::{DateTime, FixedOffset};
+
+use crate::shared::errors::CustomError;
+use crate::shared::i18n::Message;
+use crate::shared::responses::Format;
+
+/// The formats a row can be read in, and so the representations each version of it has a tag for.
+const FORMATS: [Format; 3] = [Format::Json, Format::Csv, Format::MessagePack];
+
+/// A strong ETag for a row written in `format`, derived from its id and `updated_at`, which the database bumps on
+/// every update. Representations in different formats differ byte for byte, so they get different tags.
+pub fn etag(id: i32, updated_at: &DateTime<FixedOffset>, format: Format) -> EntityTag {
+    let format = match format {
+        Format::Json => "json",
+        Format::Csv => "csv",
+        Format::MessagePack => "msgpack",
+    };
+    EntityTag::new_strong(format!("{}-{:x}-{}", id, updated_at.timestamp_micros(), format))
+}
+
+/// Reads a conditional header such as `If-Match`, or `None` if the request does not send it.
+pub fn conditional_header<H: Header>(req: &HttpRequest) -> Result<Option<H>, CustomError> {
+    if !req.headers().contains_key(H::name()) {
+        return Ok(None);
+    }
+    H::parse(req).map(Some).map_err(|_| invalid_header::<H>())
+}
+
+fn invalid_header<H: Header>() -> CustomError {
+    CustomError::BadRequestWithMsg {
+        message: Message::new("invalid-header").arg("header", H::name()),
+    }
+}
+
+/// Reads the `If-Match` header of a write, which fails with 428 if it is `required` but missing, and with 400 if
+/// it lists no tag, as the parser skips values that are not quoted tags instead of rejecting them.
+pub fn if_match(req: &HttpRequest, required: bool) -> Result<Option<IfMatch>, CustomError> {
+    match conditional_header::<IfMatch>(req)? {
+        None if required => Err(CustomError::PreconditionRequired),
+        Some(IfMatch::Items(tags)) if tags.is_empty() => Err(invalid_header::<IfMatch>()),
+        if_match => Ok(if_match),
+    }
+}
+
+/// Fails with 412 unless `If-Match` lists a tag of the current version of the row, in any format, or is `*`.
+/// Uses the strong comparison, so weak tags never match.
+pub fn check_if_match(
+    if_match: Option<&IfMatch>,
+    id: i32,
+    updated_at: &DateTime<FixedOffset>,
+) -> Result<(), CustomError> {
+    let tags = match if_match {
+        None | Some(IfMatch::Any) => return Ok(()),
+        Some(IfMatch::Items(tags)) => tags,
+    };
+    let current = FORMATS.map(|format| etag(id, updated_at, format));
+    if tags.iter().any(|tag| current.iter().any(|current| tag.strong_eq(current))) {
+        Ok(())
+    } else {
+        Err(CustomError::PreconditionFailed)
+    }
+}
+
+/// Whether the client already has the current representation, in which case a read returns 304.
+/// Uses the weak comparison, as `If-None-Match` does.
+pub fn is_not_modified(req: &HttpRequest, current: &EntityTag) -> Result<bool, CustomError> {
+    Ok(match conditional_header::<IfNoneMatch>(req)? {
+        None => false,
+        Some(IfNoneMatch::Any) => true,
+        Some(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(current)),
+    })
+}
+
+/// Returns a 304 with the current tag.
+pub fn not_modified(current: &EntityTag) -> HttpResponse {
+    let mut res = HttpResponse::NotModified().finish();
+    insert_etag(&mut res, current);
+    res
+}
+
+pub fn insert_etag(res: &mut HttpResponse, tag: &EntityTag) {
+    if let Ok(value) = HeaderValue::from_str(&tag.to_string()) {
+        res.headers_mut().insert(header::ETAG, value);
+    }
+}
+
+/// Adds the tag of a row to the response of a write, in the format the response is written in.
+pub fn insert_write_etag(res: &mut HttpResponse, id: i32, updated_at: &DateTime<FixedOffset>) {
+    let format = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(Format::from_media_type);
+    if let Some(format) = format {
+        insert_etag(res, &etag(id, updated_at, format));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+    use chrono::TimeZone;
+
+    fn updated_at(micros: u32) -> DateTime<FixedOffset> {
+        FixedOffset::east(0).ymd(2016, 11, 8).and_hms_micro(0, 0, 0, micros)
+    }
+
+    #[test]
+    fn test_etag_changes_with_updated_at_and_format() {
+        let tag = etag(1, &updated_at(1), Format::Json);
+        assert!(!tag.weak);
+        assert_eq!(tag, etag(1, &updated_at(1), Format::Json));
+        assert_ne!(tag, etag(1, &updated_at(2), Format::Json));
+        assert_ne!(tag, etag(2, &updated_at(1), Format::Json));
+        assert_ne!(tag, etag(1, &updated_at(1), Format::MessagePack));
+    }
+
+    #[test]
+    fn test_if_match() {
+        let current = etag(1, &updated_at(1), Format::Json);
+        let check = |value: &str| {
+            let req = TestRequest::default().insert_header((header::IF_MATCH, value)).to_http_request();
+            check_if_match(if_match(&req, true)?.as_ref(), 1, &updated_at(1))
+        };
+
+        assert_eq!(check(&current.to_string()), Ok(()));
+        assert_eq!(check(&etag(1, &updated_at(1), Format::MessagePack).to_string()), Ok(()));
+        assert_eq!(check(&format!(r#""other", {}"#, current)), Ok(()));
+        assert_eq!(check("*"), Ok(()));
+        assert_eq!(check(&format!("W/{}", current)), Err(CustomError::PreconditionFailed));
+        assert_eq!(
+            check(&etag(1, &updated_at(2), Format::Json).to_string()),
+            Err(CustomError::PreconditionFailed)
+        );
+        assert!(matches!(check("not quoted"), Err(CustomError::BadRequestWithMsg { .. })));
+
+        let req = TestRequest::default().to_http_request();
+        assert_eq!(if_match(&req, false), Ok(None));
+        assert_eq!(if_match(&req, true), Err(CustomError::PreconditionRequired));
+    }
+
+    #[test]
+    fn test_is_not_modified() {
+        let current = etag(1, &updated_at(1), Format::Json);
+        let check = |value: &str| {
+            let req = TestRequest::default().insert_header((header::IF_NONE_MATCH, value)).to_http_request();
+            is_not_modified(&req, &current).unwrap()
+        };
+
+        assert!(check(&current.to_string()));
+        assert!(check(&format!("W/{}", current)));
+        assert!(check("*"));
+        assert!(!check(&etag(1, &updated_at(2), Format::Json).to_string()));
+        assert!(!check(&etag(1, &updated_at(1), Format::MessagePack).to_string()));
+        assert!(!is_not_modified(&TestRequest::default().to_http_request(), &current).unwrap());
+    }
+}
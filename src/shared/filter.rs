@@ -0,0 +1,586 @@
+//! A small expression language for the `filter` query parameter of list endpoints, e.g.
+//! `done eq false and (title contains 'pay' or updated_at gt 2026-01-01)`.
+//!
+//! An expression is parsed into an `Expr`, type-checked against the fields a module exposes with `FilterField`,
+//! and compiled to a SeaORM `Condition`:
+//!
+//! - comparisons are `<field> <operator> <value>`, with the operators `eq`, `ne`, `gt`, `ge`, `lt`, `le`,
+//!   `contains`, `startswith` and `endswith`;
+//! - they combine with `and`, `or` and `not`, from the lowest precedence to the highest, and with parentheses;
+//! - values are quoted strings (`'it''s'`), integers, `true`, `false`, `null`, and timestamps written as
+//!   RFC 3339 or as a date, which means midnight UTC.
+use chrono::{DateTime, FixedOffset, NaiveDate, TimeZone};
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::{ColumnTrait, Condition};
+use std::fmt;
+
+use crate::shared::errors::CustomError;
+use crate::shared::i18n::Message;
+
+/// How deeply `not` and parentheses can nest, so that hostile input cannot exhaust the stack.
+const MAX_DEPTH: usize = 32;
+
+/// The type of a filterable field, which decides the operators and values it accepts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FieldType {
+    Integer,
+    Text,
+    Boolean,
+    Timestamp,
+}
+
+impl fmt::Display for FieldType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            FieldType::Integer => "integer",
+            FieldType::Text => "text",
+            FieldType::Boolean => "boolean",
+            FieldType::Timestamp => "timestamp",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A field that can be filtered on, as named in expressions, and the column it maps to.
+#[derive(Debug, Clone, Copy)]
+pub struct FilterField<C> {
+    pub name: &'static str,
+    pub column: C,
+    pub field_type: FieldType,
+}
+
+/// An invalid expression, with the 1-based character position of the offending token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilterError {
+    pub message: String,
+    pub position: usize,
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl From<FilterError> for CustomError {
+    fn from(e: FilterError) -> Self {
+        CustomError::BadRequestWithMsg {
+            message: Message::new("invalid-filter").arg("error", e),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Contains,
+    StartsWith,
+    EndsWith,
+}
+
+impl Operator {
+    fn parse(word: &str) -> Option<Self> {
+        let operator = match word {
+            "eq" => Operator::Eq,
+            "ne" => Operator::Ne,
+            "gt" => Operator::Gt,
+            "ge" => Operator::Ge,
+            "lt" => Operator::Lt,
+            "le" => Operator::Le,
+            "contains" => Operator::Contains,
+            "startswith" => Operator::StartsWith,
+            "endswith" => Operator::EndsWith,
+            _ => return None,
+        };
+        Some(operator)
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Operator::Eq => "eq",
+            Operator::Ne => "ne",
+            Operator::Gt => "gt",
+            Operator::Ge => "ge",
+            Operator::Lt => "lt",
+            Operator::Le => "le",
+            Operator::Contains => "contains",
+            Operator::StartsWith => "startswith",
+            Operator::EndsWith => "endswith",
+        }
+    }
+
+    fn accepts(&self, field_type: FieldType) -> bool {
+        match self {
+            Operator::Eq | Operator::Ne => true,
+            Operator::Gt | Operator::Ge | Operator::Lt | Operator::Le => field_type != FieldType::Boolean,
+            Operator::Contains | Operator::StartsWith | Operator::EndsWith => field_type == FieldType::Text,
+        }
+    }
+}
+
+/// A value as written in the expression, before it is checked against the type of its field.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    /// A quoted string.
+    String(String),
+    /// An unquoted word, such as a number or a date.
+    Bare(String),
+    Bool(bool),
+    Null,
+}
+
+/// The syntax tree of an expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare {
+        field: String,
+        operator: Operator,
+        value: Literal,
+        /// The positions of the field and of the value, to point at them in type errors.
+        field_position: usize,
+        value_position: usize,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Word(String),
+    String(String),
+    LeftParen,
+    RightParen,
+    End,
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TokenKind::Word(word) => write!(f, "`{}`", word),
+            TokenKind::String(value) => write!(f, "'{}'", value.replace('\'', "''")),
+            TokenKind::LeftParen => f.write_str("`(`"),
+            TokenKind::RightParen => f.write_str("`)`"),
+            TokenKind::End => f.write_str("end of filter"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    position: usize,
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | ':' | '.' | '+')
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, FilterError> {
+    let mut tokens = vec![];
+    let mut chars = input.chars().enumerate().peekable();
+    while let Some((index, c)) = chars.next() {
+        let position = index + 1;
+        let kind = match c {
+            c if c.is_whitespace() => continue,
+            '(' => TokenKind::LeftParen,
+            ')' => TokenKind::RightParen,
+            '\'' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '\'')) if matches!(chars.peek(), Some((_, '\''))) => {
+                            chars.next();
+                            value.push('\'');
+                        }
+                        Some((_, '\'')) => break,
+                        Some((_, c)) => value.push(c),
+                        None => {
+                            return Err(FilterError {
+                                message: "Unterminated string".to_owned(),
+                                position,
+                            })
+                        }
+                    }
+                }
+                TokenKind::String(value)
+            }
+            c if is_word_char(c) => {
+                let mut word = c.to_string();
+                while let Some((_, c)) = chars.peek().filter(|(_, c)| is_word_char(*c)) {
+                    word.push(*c);
+                    chars.next();
+                }
+                TokenKind::Word(word)
+            }
+            c => {
+                return Err(FilterError {
+                    message: format!("Unexpected character `{}`", c),
+                    position,
+                })
+            }
+        };
+        tokens.push(Token { kind, position });
+    }
+    tokens.push(Token {
+        kind: TokenKind::End,
+        position: input.chars().count() + 1,
+    });
+    Ok(tokens)
+}
+
+/// A recursive descent parser over the tokens, one method per precedence level.
+struct Parser {
+    tokens: Vec<Token>,
+    next: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.next]
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.next].clone();
+        if token.kind != TokenKind::End {
+            self.next += 1;
+        }
+        token
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(&self.peek().kind, TokenKind::Word(word) if word == keyword)
+    }
+
+    fn unexpected(token: &Token, expected: &str) -> FilterError {
+        FilterError {
+            message: format!("Expected {}, found {}", expected, token.kind),
+            position: token.position,
+        }
+    }
+
+    fn nest(&mut self) -> Result<(), FilterError> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(FilterError {
+                message: format!("Filter is nested more than {} levels deep", MAX_DEPTH),
+                position: self.peek().position,
+            });
+        }
+        Ok(())
+    }
+
+    fn or(&mut self) -> Result<Expr, FilterError> {
+        let mut expr = self.and()?;
+        while self.is_keyword("or") {
+            self.advance();
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, FilterError> {
+        let mut expr = self.not()?;
+        while self.is_keyword("and") {
+            self.advance();
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr, FilterError> {
+        if !self.is_keyword("not") {
+            return self.primary();
+        }
+        self.advance();
+        self.nest()?;
+        let expr = Expr::Not(Box::new(self.not()?));
+        self.depth -= 1;
+        Ok(expr)
+    }
+
+    fn primary(&mut self) -> Result<Expr, FilterError> {
+        let token = self.advance();
+        match token.kind {
+            TokenKind::LeftParen => {
+                self.nest()?;
+                let expr = self.or()?;
+                self.depth -= 1;
+                let token = self.advance();
+                if token.kind != TokenKind::RightParen {
+                    return Err(Self::unexpected(&token, "`)`"));
+                }
+                Ok(expr)
+            }
+            TokenKind::Word(field) => self.comparison(field, token.position),
+            _ => Err(Self::unexpected(&token, "a field name or `(`")),
+        }
+    }
+
+    fn comparison(&mut self, field: String, field_position: usize) -> Result<Expr, FilterError> {
+        let token = self.advance();
+        let operator = match &token.kind {
+            TokenKind::Word(word) => Operator::parse(word),
+            _ => None,
+        }
+        .ok_or_else(|| Self::unexpected(&token, &format!("an operator after `{}`", field)))?;
+
+        let token = self.advance();
+        let value = match token.kind {
+            TokenKind::String(value) => Literal::String(value),
+            TokenKind::Word(word) => match word.as_str() {
+                "true" => Literal::Bool(true),
+                "false" => Literal::Bool(false),
+                "null" => Literal::Null,
+                _ => Literal::Bare(word),
+            },
+            _ => return Err(Self::unexpected(&token, "a value")),
+        };
+        Ok(Expr::Compare {
+            field,
+            operator,
+            value,
+            field_position,
+            value_position: token.position,
+        })
+    }
+}
+
+/// Parses an expression into its syntax tree, without looking at the fields it refers to.
+pub fn parse(input: &str) -> Result<Expr, FilterError> {
+    let mut parser = Parser {
+        tokens: tokenize(input)?,
+        next: 0,
+        depth: 0,
+    };
+    let expr = parser.or()?;
+    let token = parser.advance();
+    if token.kind != TokenKind::End {
+        return Err(Parser::unexpected(&token, "`and`, `or` or the end of the filter"));
+    }
+    Ok(expr)
+}
+
+/// A value converted to the type of its field.
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Integer(i32),
+    Text(String),
+    Boolean(bool),
+    Timestamp(DateTimeWithTimeZone),
+    Null,
+}
+
+fn parse_timestamp(value: &str) -> Option<DateTimeWithTimeZone> {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(value) {
+        return Some(timestamp);
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+    Some(FixedOffset::east(0).from_utc_datetime(&date.and_hms(0, 0, 0)))
+}
+
+fn check_value(
+    field: &str,
+    field_type: FieldType,
+    operator: Operator,
+    value: &Literal,
+    position: usize,
+) -> Result<Value, FilterError> {
+    let checked = match (field_type, value) {
+        (_, Literal::Null) if matches!(operator, Operator::Eq | Operator::Ne) => Some(Value::Null),
+        (FieldType::Integer, Literal::Bare(value)) => value.parse().ok().map(Value::Integer),
+        (FieldType::Text, Literal::String(value)) => Some(Value::Text(value.clone())),
+        (FieldType::Boolean, Literal::Bool(value)) => Some(Value::Boolean(*value)),
+        (FieldType::Timestamp, Literal::Bare(value) | Literal::String(value)) => {
+            parse_timestamp(value).map(Value::Timestamp)
+        }
+        _ => None,
+    };
+    checked.ok_or_else(|| {
+        let value = match value {
+            Literal::String(value) => TokenKind::String(value.clone()).to_string(),
+            Literal::Bare(value) => format!("`{}`", value),
+            Literal::Bool(value) => format!("`{}`", value),
+            Literal::Null => "`null`".to_owned(),
+        };
+        FilterError {
+            message: format!("{} is not a valid value for {} field `{}`", value, field_type, field),
+            position,
+        }
+    })
+}
+
+fn compare<C: ColumnTrait>(column: C, operator: Operator, value: Value) -> Condition {
+    let expr = match (operator, value) {
+        (Operator::Eq, Value::Null) => column.is_null(),
+        (Operator::Ne, Value::Null) => column.is_not_null(),
+        (Operator::Contains, Value::Text(value)) => column.contains(&value),
+        (Operator::StartsWith, Value::Text(value)) => column.starts_with(&value),
+        (Operator::EndsWith, Value::Text(value)) => column.ends_with(&value),
+        (operator, value) => {
+            let value: sea_orm::Value = match value {
+                Value::Integer(value) => value.into(),
+                Value::Text(value) => value.into(),
+                Value::Boolean(value) => value.into(),
+                Value::Timestamp(value) => value.into(),
+                Value::Null => unreachable!("null is only accepted by eq and ne"),
+            };
+            match operator {
+                Operator::Eq => column.eq(value),
+                Operator::Ne => column.ne(value),
+                Operator::Gt => column.gt(value),
+                Operator::Ge => column.gte(value),
+                Operator::Lt => column.lt(value),
+                Operator::Le => column.lte(value),
+                Operator::Contains | Operator::StartsWith | Operator::EndsWith => {
+                    unreachable!("text operators only accept text values")
+                }
+            }
+        }
+    };
+    Condition::all().add(expr)
+}
+
+/// Type-checks an expression against the fields that can be filtered on and compiles it to a condition.
+pub fn compile<C: ColumnTrait>(expr: &Expr, fields: &[FilterField<C>]) -> Result<Condition, FilterError> {
+    let condition = match expr {
+        Expr::And(left, right) => Condition::all()
+            .add(compile(left, fields)?)
+            .add(compile(right, fields)?),
+        Expr::Or(left, right) => Condition::any()
+            .add(compile(left, fields)?)
+            .add(compile(right, fields)?),
+        Expr::Not(expr) => compile(expr, fields)?.not(),
+        Expr::Compare {
+            field,
+            operator,
+            value,
+            field_position,
+            value_position,
+        } => {
+            let filter_field = fields.iter().find(|f| f.name == field.as_str()).ok_or_else(|| FilterError {
+                message: format!(
+                    "Unknown field `{}`, expected one of: {}",
+                    field,
+                    fields.iter().map(|f| f.name).collect::<Vec<_>>().join(", ")
+                ),
+                position: *field_position,
+            })?;
+            if !operator.accepts(filter_field.field_type) {
+                return Err(FilterError {
+                    message: format!(
+                        "Operator `{}` cannot be applied to {} field `{}`",
+                        operator.as_str(),
+                        filter_field.field_type,
+                        field
+                    ),
+                    position: *field_position,
+                });
+            }
+            let value = check_value(field, filter_field.field_type, *operator, value, *value_position)?;
+            compare(filter_field.column, *operator, value)
+        }
+    };
+    Ok(condition)
+}
+
+/// Parses, type-checks and compiles the `filter` query parameter of a list endpoint.
+pub fn parse_filter<C: ColumnTrait>(input: &str, fields: &[FilterField<C>]) -> Result<Condition, CustomError> {
+    Ok(compile(&parse(input)?, fields)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use entity::todo;
+    use sea_orm::{DbBackend, EntityTrait, QueryFilter, QueryTrait};
+
+    const FIELDS: &[FilterField<todo::Column>] = &[
+        FilterField { name: "id", column: todo::Column::Id, field_type: FieldType::Integer },
+        FilterField { name: "title", column: todo::Column::Title, field_type: FieldType::Text },
+        FilterField { name: "done", column: todo::Column::Done, field_type: FieldType::Boolean },
+        FilterField { name: "updated_at", column: todo::Column::UpdatedAt, field_type: FieldType::Timestamp },
+    ];
+
+    fn where_clause(input: &str) -> String {
+        let sql = todo::Entity::find()
+            .filter(parse_filter(input, FIELDS).unwrap())
+            .build(DbBackend::Postgres)
+            .to_string();
+        sql.split_once(" WHERE ").unwrap().1.to_owned()
+    }
+
+    fn error(input: &str) -> FilterError {
+        parse(input).and_then(|expr| compile(&expr, FIELDS)).unwrap_err()
+    }
+
+    #[test]
+    fn test_parse_precedence() {
+        let compare = |field: &str, value: Literal, field_position, value_position| Expr::Compare {
+            field: field.to_owned(),
+            operator: Operator::Eq,
+            value,
+            field_position,
+            value_position,
+        };
+        assert_eq!(
+            parse("not done eq true or id eq 1 and title eq 'it''s'").unwrap(),
+            Expr::Or(
+                Box::new(Expr::Not(Box::new(compare("done", Literal::Bool(true), 5, 13)))),
+                Box::new(Expr::And(
+                    Box::new(compare("id", Literal::Bare("1".to_owned()), 21, 27)),
+                    Box::new(compare("title", Literal::String("it's".to_owned()), 33, 42)),
+                )),
+            )
+        );
+    }
+
+    #[test]
+    fn test_compile() {
+        assert_eq!(
+            where_clause("done eq false and (title contains 'pay' or id ge 3)"),
+            r#""todo"."done" = FALSE AND ("todo"."title" LIKE '%pay%' OR "todo"."id" >= 3)"#
+        );
+        assert_eq!(where_clause("not title eq null"), r#"NOT ("todo"."title" IS NULL)"#);
+        assert!(parse_filter("updated_at gt 2026-01-01", FIELDS).is_ok());
+        assert!(parse_filter("updated_at le '2026-01-01T12:00:00+02:00'", FIELDS).is_ok());
+    }
+
+    #[test]
+    fn test_errors_point_at_the_offending_token() {
+        let cases = [
+            ("done eq", "Expected a value, found end of filter", 8),
+            ("done is true", "Expected an operator after `done`, found `is`", 6),
+            ("(done eq true", "Expected `)`, found end of filter", 14),
+            ("done eq true or", "Expected a field name or `(`, found end of filter", 16),
+            ("done eq true id eq 1", "Expected `and`, `or` or the end of the filter, found `id`", 14),
+            ("title eq 'pay", "Unterminated string", 10),
+            ("title eq \"pay\"", "Unexpected character `\"`", 10),
+            ("owner eq 1", "Unknown field `owner`, expected one of: id, title, done, updated_at", 1),
+            ("done contains 'x'", "Operator `contains` cannot be applied to boolean field `done`", 1),
+            ("id eq 'one'", "'one' is not a valid value for integer field `id`", 7),
+            ("updated_at gt yesterday", "`yesterday` is not a valid value for timestamp field `updated_at`", 15),
+            ("id gt null", "`null` is not a valid value for integer field `id`", 7),
+        ];
+        for (input, message, position) in cases {
+            assert_eq!(
+                error(input),
+                FilterError { message: message.to_owned(), position },
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_nesting_is_limited() {
+        let input = format!("{}done eq true{}", "(".repeat(MAX_DEPTH + 1), ")".repeat(MAX_DEPTH + 1));
+        assert!(error(&input).message.contains("nested"));
+    }
+}
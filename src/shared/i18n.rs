@@ -0,0 +1,246 @@
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource, FluentValue};
+use once_cell::sync::Lazy;
+use serde_json::Value;
+use std::borrow::Cow;
+use std::fmt;
+use unic_langid::LanguageIdentifier;
+use validator::{ValidationError, ValidationErrors, ValidationErrorsKind};
+
+/// The locale used when the client accepts none of the shipped locales.
+pub const DEFAULT_LOCALE: &str = "en";
+
+/// The shipped locales and their message catalogs, with the default locale first.
+const LOCALES: &[(&str, &str)] = &[
+    ("en", include_str!("../../locales/en/errors.ftl")),
+    ("es", include_str!("../../locales/es/errors.ftl")),
+];
+
+/// Resolves messages from the Fluent catalogs under `locales/`.
+pub struct Localizer {
+    bundles: Vec<(&'static str, FluentBundle<FluentResource>)>,
+}
+
+impl Localizer {
+    fn new() -> Self {
+        let bundles = LOCALES
+            .iter()
+            .map(|(locale, source)| {
+                let langid: LanguageIdentifier = locale.parse().expect("Invalid locale");
+                let resource = FluentResource::try_new(source.to_string())
+                    .unwrap_or_else(|_| panic!("Failed to parse the {} message catalog", locale));
+                let mut bundle = FluentBundle::new_concurrent(vec![langid]);
+                bundle.set_use_isolating(false);
+                bundle
+                    .add_resource(resource)
+                    .unwrap_or_else(|_| panic!("Duplicate messages in the {} message catalog", locale));
+                (*locale, bundle)
+            })
+            .collect();
+        Self { bundles }
+    }
+
+    /// Picks the best shipped locale for an `Accept-Language` header value, falling back to the default locale.
+    pub fn negotiate(&self, accept_language: Option<&str>) -> &'static str {
+        let mut ranges: Vec<(&str, f32)> = accept_language
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|range| {
+                let mut parts = range.split(';');
+                let tag = parts.next()?.trim();
+                let quality = parts
+                    .find_map(|param| param.trim().strip_prefix("q="))
+                    .and_then(|q| q.parse().ok())
+                    .unwrap_or(1.0);
+                (!tag.is_empty() && quality > 0.0).then_some((tag, quality))
+            })
+            .collect();
+        ranges.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+
+        ranges
+            .iter()
+            .find_map(|(tag, _)| {
+                let language = tag.split('-').next().unwrap_or_default().to_lowercase();
+                self.bundles
+                    .iter()
+                    .map(|(locale, _)| *locale)
+                    .find(|locale| *locale == language)
+            })
+            .unwrap_or(DEFAULT_LOCALE)
+    }
+
+    fn format(&self, locale: &str, id: &str, args: Option<&FluentArgs>) -> Option<String> {
+        let (_, bundle) = self.bundles.iter().find(|(name, _)| *name == locale)?;
+        let pattern = bundle.get_message(id)?.value()?;
+        let mut errors = vec![];
+        Some(bundle.format_pattern(pattern, args, &mut errors).into_owned())
+    }
+
+    /// Resolves a message in the given locale, falling back to the default locale.
+    pub fn message(&self, locale: &str, id: &str, args: Option<&FluentArgs>) -> Option<String> {
+        self.format(locale, id, args)
+            .or_else(|| self.format(DEFAULT_LOCALE, id, args))
+    }
+
+    /// Resolves the message of a single validation error from its code and parameters.
+    fn validation_message(&self, locale: &str, error: &ValidationError) -> String {
+        let mut args = FluentArgs::new();
+        let mut bounds = vec![];
+        for (name, value) in &error.params {
+            if ["min", "max", "equal"].contains(&name.as_ref()) {
+                bounds.push(name.as_ref());
+            }
+            args.set(name.to_string(), fluent_value(value));
+        }
+        bounds.sort_by_key(|bound| ["min", "max", "equal"].iter().position(|b| b == bound));
+
+        let specific = format!("validation-{}-{}", error.code, bounds.join("-"));
+        let generic = format!("validation-{}", error.code);
+        self.message(locale, &specific, Some(&args))
+            .or_else(|| self.message(locale, &generic, Some(&args)))
+            .or_else(|| self.message(locale, "validation-default", None))
+            .unwrap_or_else(|| error.code.to_string())
+    }
+
+    /// Returns a copy of `errors` where every error carries a message in the given locale.
+    pub fn localize_validation_errors(&self, locale: &str, errors: &ValidationErrors) -> ValidationErrors {
+        let mut errors = errors.clone();
+        self.localize_in_place(locale, &mut errors);
+        errors
+    }
+
+    fn localize_in_place(&self, locale: &str, errors: &mut ValidationErrors) {
+        for kind in errors.errors_mut().values_mut() {
+            match kind {
+                ValidationErrorsKind::Field(field_errors) => {
+                    for error in field_errors.iter_mut() {
+                        if error.message.is_none() {
+                            error.message = Some(Cow::Owned(self.validation_message(locale, error)));
+                        }
+                    }
+                }
+                ValidationErrorsKind::Struct(nested) => self.localize_in_place(locale, nested),
+                ValidationErrorsKind::List(items) => {
+                    for nested in items.values_mut() {
+                        self.localize_in_place(locale, nested);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A message built from client input, such as a rejected query parameter, resolved from the catalogs when the error
+/// carrying it is rendered. The details it is built from are passed as arguments rather than formatted in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    id: &'static str,
+    args: Vec<(&'static str, String)>,
+}
+
+impl Message {
+    pub fn new(id: &'static str) -> Self {
+        Self { id, args: vec![] }
+    }
+
+    pub fn arg(mut self, name: &'static str, value: impl ToString) -> Self {
+        self.args.push((name, value.to_string()));
+        self
+    }
+
+    /// Resolves this message in the given locale, falling back to the default locale and then to its id.
+    pub fn localize(&self, locale: &str) -> String {
+        let mut args = FluentArgs::new();
+        for (name, value) in &self.args {
+            args.set(*name, value.clone());
+        }
+        localizer()
+            .message(locale, self.id, Some(&args))
+            .unwrap_or_else(|| self.id.to_owned())
+    }
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.localize(DEFAULT_LOCALE))
+    }
+}
+
+fn fluent_value(value: &Value) -> FluentValue<'static> {
+    match value {
+        Value::Number(number) => match number.as_i64() {
+            Some(number) => FluentValue::from(number),
+            None => FluentValue::from(number.as_f64().unwrap_or_default()),
+        },
+        Value::String(s) => FluentValue::from(s.clone()),
+        other => FluentValue::from(other.to_string()),
+    }
+}
+
+static LOCALIZER: Lazy<Localizer> = Lazy::new(Localizer::new);
+
+/// Returns the shared localizer, loading the catalogs on first use.
+pub fn localizer() -> &'static Localizer {
+    &LOCALIZER
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate() {
+        let localizer = localizer();
+        assert_eq!(localizer.negotiate(None), "en");
+        assert_eq!(localizer.negotiate(Some("es-ES,es;q=0.9,en;q=0.8")), "es");
+        assert_eq!(localizer.negotiate(Some("fr-FR,es;q=0.5,en;q=0.8")), "en");
+        assert_eq!(localizer.negotiate(Some("fr-FR")), "en");
+        assert_eq!(localizer.negotiate(Some("es;q=0")), "en");
+    }
+
+    #[test]
+    fn test_message_falls_back_to_default_locale() {
+        let localizer = localizer();
+        assert_eq!(localizer.message("es", "TODO_NOT_FOUND", None).unwrap(), "Tarea no encontrada");
+        assert_eq!(localizer.message("fr", "TODO_NOT_FOUND", None).unwrap(), "Todo not found");
+        assert_eq!(localizer.message("es", "does-not-exist", None), None);
+    }
+
+    #[test]
+    fn test_message_with_args() {
+        let message = Message::new("limit-out-of-range").arg("max", 20);
+        assert_eq!(message.to_string(), "limit must be between 1 and 20");
+        assert_eq!(message.localize("es"), "limit debe estar entre 1 y 20");
+        assert_eq!(Message::new("does-not-exist").localize("es"), "does-not-exist");
+    }
+
+    #[test]
+    fn test_localize_validation_errors() {
+        let mut error = ValidationError::new("length");
+        error.add_param(Cow::from("min"), &1);
+        error.add_param(Cow::from("max"), &30);
+        let mut errors = ValidationErrors::new();
+        errors.add("title", error);
+
+        let localized = localizer().localize_validation_errors("es", &errors);
+        let message = localized.field_errors()["title"][0].message.clone().unwrap();
+        assert_eq!(message, "Debe tener entre 1 y 30 caracteres.");
+    }
+
+    #[test]
+    fn test_catalogs_have_the_same_messages() {
+        let ids = |source: &str| -> Vec<String> {
+            let mut ids: Vec<String> = source
+                .lines()
+                .filter(|line| !line.starts_with('#') && line.contains(" = "))
+                .map(|line| line.split(" = ").next().unwrap().to_owned())
+                .collect();
+            ids.sort();
+            ids
+        };
+        let default_ids = ids(LOCALES[0].1);
+        for (locale, source) in LOCALES {
+            assert_eq!(ids(source), default_ids, "the {} catalog is missing messages", locale);
+        }
+    }
+}
@@ -1,2 +1,8 @@
+pub mod cursor;
+pub mod error_codes;
 pub mod errors;
+pub mod etag;
+pub mod filter;
+pub mod i18n;
+pub mod requests;
 pub mod responses;
\ No newline at end of file
@@ -0,0 +1,148 @@
+use actix_web::{dev::Payload, http::header, web, FromRequest, HttpRequest};
+use futures_util::future::LocalBoxFuture;
+use futures_util::StreamExt;
+use serde::de::DeserializeOwned;
+use std::ops::Deref;
+
+use crate::shared::errors::CustomError;
+use crate::shared::i18n::Message;
+use crate::shared::responses::Format;
+
+/// The largest CSV or MessagePack body accepted, the same as the default limit of `web::Json`.
+const MAX_BODY_SIZE: usize = 2 * 1024 * 1024;
+
+/// Extracts a request body in the format of its `Content-Type`: JSON, MessagePack, or CSV with a header row and
+/// a single record. JSON bodies are read by `web::Json`, so its `JsonConfig` still applies to them.
+pub struct Body<T>(pub T);
+
+impl<T> Body<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Body<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+fn decode<T: DeserializeOwned>(format: Format, bytes: &[u8]) -> Result<T, CustomError> {
+    let invalid = |e: &dyn std::fmt::Display| CustomError::BadRequestWithMsg {
+        message: Message::new("invalid-request-body").arg("error", e),
+    };
+    match format {
+        Format::MessagePack => rmp_serde::from_slice(bytes).map_err(|e| invalid(&e)),
+        Format::Csv => {
+            let mut records = csv::Reader::from_reader(bytes).into_deserialize::<T>();
+            let record = match records.next() {
+                Some(record) => record.map_err(|e| invalid(&e))?,
+                None => {
+                    return Err(CustomError::BadRequestWithMsg {
+                        message: Message::new("csv-body-without-record"),
+                    })
+                }
+            };
+            if records.next().is_some() {
+                return Err(CustomError::BadRequestWithMsg {
+                    message: Message::new("csv-body-with-several-records"),
+                });
+            }
+            Ok(record)
+        }
+        Format::Json => serde_json::from_slice(bytes).map_err(|e| invalid(&e)),
+    }
+}
+
+impl<T: DeserializeOwned + 'static> FromRequest for Body<T> {
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let content_type = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok());
+        let format = match content_type.map(Format::from_media_type) {
+            None | Some(Some(Format::Json)) => {
+                let json = web::Json::<T>::from_request(req, payload);
+                return Box::pin(async move { Ok(Body(json.await?.into_inner())) });
+            }
+            Some(Some(format)) => format,
+            Some(None) => {
+                let e = CustomError::UnsupportedMediaType {
+                    message: Message::new("unsupported-content-type").arg(
+                        "expected",
+                        [Format::Json, Format::Csv, Format::MessagePack]
+                            .map(|format| format.content_type())
+                            .join(", "),
+                    ),
+                };
+                return Box::pin(async move { Err(e.into()) });
+            }
+        };
+
+        let mut payload = payload.take();
+        Box::pin(async move {
+            let mut bytes = web::BytesMut::new();
+            while let Some(chunk) = payload.next().await {
+                let chunk = chunk.map_err(|e| CustomError::BadRequestWithMsg {
+                    message: Message::new("invalid-request-body").arg("error", e),
+                })?;
+                if bytes.len() + chunk.len() > MAX_BODY_SIZE {
+                    return Err(CustomError::PayloadTooLarge {
+                        message: Message::new("body-too-large").arg("limit", MAX_BODY_SIZE),
+                    }
+                    .into());
+                }
+                bytes.extend_from_slice(&chunk);
+            }
+            Ok(Body(decode(format, &bytes)?))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Todo {
+        title: String,
+        done: bool,
+        description: Option<String>,
+    }
+
+    #[test]
+    fn test_decode_csv() {
+        let todo: Todo = decode(Format::Csv, b"title,done,description\nPay rent,false,\n").unwrap();
+        assert_eq!(
+            todo,
+            Todo {
+                title: "Pay rent".to_owned(),
+                done: false,
+                description: None,
+            }
+        );
+
+        for body in [&b"title,done,description\n"[..], b"title,done\na,true\nb,false\n", b"title,done\na,maybe\n"] {
+            assert!(matches!(decode::<Todo>(Format::Csv, body), Err(CustomError::BadRequestWithMsg { .. })));
+        }
+    }
+
+    #[test]
+    fn test_decode_msgpack() {
+        #[derive(serde::Serialize)]
+        struct Input<'a> {
+            title: &'a str,
+            done: bool,
+        }
+        let body = rmp_serde::to_vec_named(&Input { title: "Pay rent", done: true }).unwrap();
+        let todo: Todo = decode(Format::MessagePack, &body).unwrap();
+        assert_eq!(todo.title, "Pay rent");
+        assert!(todo.done);
+    }
+}
@@ -1,6 +1,189 @@
-use actix_web::{HttpResponse, Responder, HttpRequest, body::BoxBody, http::StatusCode};
+use actix_web::{HttpResponse, Responder, HttpRequest, body::BoxBody, http::{header::{self, ContentType, HeaderValue}, StatusCode}};
 use derive_more::Display;
 use serde::Serialize;
+use std::time::Instant;
+
+use crate::core::middleware::server_timing::record_serialization_time;
+use crate::shared::errors::{CustomError, ErrorSource};
+use crate::shared::i18n::Message;
+
+/// Serializes `body` into a JSON response, recording the time spent for the `Server-Timing` header.
+pub fn json_response<T: Serialize>(status_code: StatusCode, body: &T) -> HttpResponse {
+    let start = Instant::now();
+    let body = serde_json::to_vec(body);
+    record_serialization_time(start.elapsed());
+    match body {
+        Ok(body) => HttpResponse::build(status_code)
+            .content_type(ContentType::json())
+            .body(body),
+        Err(e) => HttpResponse::from_error(e),
+    }
+}
+
+/// The formats bodies can be written and read in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Json,
+    /// A header row and one row per item. Only lists can be written as CSV.
+    Csv,
+    MessagePack,
+}
+
+impl Format {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Format::Json => "application/json",
+            Format::Csv => "text/csv; charset=utf-8",
+            Format::MessagePack => "application/msgpack",
+        }
+    }
+
+    /// Returns the format of a media type, ignoring its parameters.
+    pub fn from_media_type(media_type: &str) -> Option<Self> {
+        let essence = media_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+        match essence.as_str() {
+            "application/json" => Some(Format::Json),
+            "text/csv" => Some(Format::Csv),
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => Some(Format::MessagePack),
+            _ => None,
+        }
+    }
+
+    /// Picks the format the `Accept` header prefers among `offered`, which lists the server's preference first.
+    /// Responds in the first offered format when the header is missing.
+    pub fn negotiate(req: &HttpRequest, offered: &[Format]) -> Result<Self, CustomError> {
+        let accept = match req.headers().get(header::ACCEPT).and_then(|value| value.to_str().ok()) {
+            Some(accept) if !accept.trim().is_empty() => accept,
+            _ => return Ok(offered[0]),
+        };
+        let ranges: Vec<(String, f32)> = accept
+            .split(',')
+            .map(|range| {
+                let mut params = range.split(';');
+                let media_range = params.next().unwrap_or_default().trim().to_ascii_lowercase();
+                let quality = params
+                    .filter_map(|param| param.trim().strip_prefix("q="))
+                    .find_map(|q| q.trim().parse().ok())
+                    .unwrap_or(1.0);
+                (media_range, quality)
+            })
+            .collect();
+        // The quality of a format is that of the most specific range matching it, so `q=0` on a media type
+        // excludes it even if a wildcard accepts everything else.
+        let quality_of = |format: Format| {
+            ranges
+                .iter()
+                .filter_map(|(range, quality)| {
+                    let specificity = match range.as_str() {
+                        "*/*" => 0,
+                        range if range.ends_with("/*") => {
+                            if !format.content_type().starts_with(&range[..range.len() - 1]) {
+                                return None;
+                            }
+                            1
+                        }
+                        range if Format::from_media_type(range) == Some(format) => 2,
+                        _ => return None,
+                    };
+                    Some((specificity, *quality))
+                })
+                .max_by_key(|(specificity, _)| *specificity)
+                .map_or(0.0, |(_, quality)| quality)
+        };
+
+        // Equally preferred formats are picked in the server's order
+        let mut best: Option<(Format, f32)> = None;
+        for format in offered.iter().copied() {
+            let quality = quality_of(format);
+            if quality > 0.0 && best.is_none_or(|(_, best)| quality > best) {
+                best = Some((format, quality));
+            }
+        }
+        best.map(|(format, _)| format).ok_or_else(|| CustomError::NotAcceptable {
+            message: Message::new("not-acceptable").arg("accept", accept).arg(
+                "available",
+                offered.iter().map(|format| format.content_type()).collect::<Vec<_>>().join(", "),
+            ),
+        })
+    }
+}
+
+/// Writes `items` as CSV under a header row of `columns`, which is written even if there are no items.
+/// Nested values are written as JSON, and fields missing from an item as empty cells.
+/// Text starting with `=`, `+`, `-` or `@` is prefixed with `'` so that spreadsheets do not run it as a formula.
+fn to_csv<S: AsRef<str>, I: Serialize>(
+    columns: &[S],
+    items: &[I],
+) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    let rows = items
+        .iter()
+        .map(serde_json::to_value)
+        .collect::<Result<Vec<serde_json::Value>, _>>()?;
+    let columns: Vec<&str> = columns.iter().map(AsRef::as_ref).collect();
+
+    let mut writer = csv::Writer::from_writer(vec![]);
+    writer.write_record(&columns)?;
+    for row in &rows {
+        let cells = columns.iter().map(|column| match row.get(*column) {
+            None | Some(serde_json::Value::Null) => String::new(),
+            Some(serde_json::Value::String(text)) if text.starts_with(&['=', '+', '-', '@'][..]) => format!("'{}", text),
+            Some(serde_json::Value::String(text)) => text.to_owned(),
+            Some(value) => value.to_string(),
+        });
+        writer.write_record(cells)?;
+    }
+    Ok(writer.into_inner()?)
+}
+
+/// Serializes `body` in the format the client accepts, or the items of `list` under its columns if the client
+/// wants CSV. Only JSON and MessagePack are offered when there is no `list`.
+fn negotiate<T: Serialize, S: AsRef<str>, I: Serialize>(
+    req: &HttpRequest,
+    status_code: StatusCode,
+    body: &T,
+    list: Option<(&[S], &[I])>,
+) -> HttpResponse {
+    let offered: &[Format] = match list {
+        Some(_) => &[Format::Json, Format::Csv, Format::MessagePack],
+        None => &[Format::Json, Format::MessagePack],
+    };
+    let format = match Format::negotiate(req, offered) {
+        Ok(format) => format,
+        Err(e) => return HttpResponse::from_error(e),
+    };
+
+    let start = Instant::now();
+    let encoded: Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> = match (format, list) {
+        (Format::Csv, Some((columns, items))) => to_csv(columns, items),
+        (Format::MessagePack, _) => rmp_serde::to_vec_named(body).map_err(Into::into),
+        _ => serde_json::to_vec(body).map_err(Into::into),
+    };
+    record_serialization_time(start.elapsed());
+    match encoded {
+        Ok(body) => HttpResponse::build(status_code)
+            .content_type(format.content_type())
+            .insert_header((header::VARY, "Accept"))
+            .body(body),
+        Err(e) => HttpResponse::from_error(CustomError::Internal { source: ErrorSource::from(e) }),
+    }
+}
+
+/// Serializes `body` as JSON or MessagePack, according to the `Accept` header.
+pub fn negotiated_response<T: Serialize>(req: &HttpRequest, status_code: StatusCode, body: &T) -> HttpResponse {
+    negotiate::<T, &str, ()>(req, status_code, body, None)
+}
+
+/// Serializes `body` as JSON or MessagePack, or its `items` as CSV with a header row of `columns`, according to
+/// the `Accept` header.
+pub fn negotiated_list_response<T: Serialize, S: AsRef<str>, I: Serialize>(
+    req: &HttpRequest,
+    status_code: StatusCode,
+    body: &T,
+    columns: &[S],
+    items: &[I],
+) -> HttpResponse {
+    negotiate(req, status_code, body, Some((columns, items)))
+}
 
 #[derive(Serialize)]
 struct FormattedResponseWithId {
@@ -47,19 +230,334 @@ impl CustomResponse {
 
 impl Responder for CustomResponse {
     type Body = BoxBody;
-    fn respond_to(self, _: &HttpRequest) -> HttpResponse {
+    fn respond_to(self, req: &HttpRequest) -> HttpResponse {
         if let CustomResponse::BulkDeleted { ids } = &self {
             let response = FormattedResponseWithIds {
                 message: self.to_string(),
                 ids: ids.to_owned()
             };
-            HttpResponse::build(self.status_code()).json(response)
+            negotiated_response(req, self.status_code(), &response)
         } else {
             let response = FormattedResponseWithId {
                 message: self.to_string(),
                 id: self.id().unwrap(),
             };
-            HttpResponse::build(self.status_code()).json(response)
+            negotiated_response(req, self.status_code(), &response)
+        }
+    }
+}
+
+/// What a client asked writes to return, via the `Prefer` header (RFC 7240).
+#[derive(Debug, PartialEq)]
+pub enum ReturnPreference {
+    Representation,
+    Minimal,
+}
+
+impl ReturnPreference {
+    /// Reads `return=minimal` or `return=representation` from the `Prefer` header, defaulting to the representation.
+    pub fn from_request(req: &HttpRequest) -> Self {
+        let minimal = req
+            .headers()
+            .get_all(header::HeaderName::from_static("prefer"))
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .any(|preference| preference.trim().eq_ignore_ascii_case("return=minimal"));
+        if minimal {
+            ReturnPreference::Minimal
+        } else {
+            ReturnPreference::Representation
+        }
+    }
+}
+
+/// Responds to a write with the persisted resource, or with the bare `CustomResponse` if the client prefers `return=minimal`.
+/// Created resources also get a `Location` header.
+pub fn write_response<T: Serialize>(
+    req: &HttpRequest,
+    response: CustomResponse,
+    resource: &T,
+    location: Option<String>,
+) -> HttpResponse {
+    let (mut res, applied) = match ReturnPreference::from_request(req) {
+        ReturnPreference::Minimal => (response.respond_to(req), "return=minimal"),
+        ReturnPreference::Representation => (
+            negotiated_response(req, response.status_code(), resource),
+            "return=representation",
+        ),
+    };
+    res.headers_mut().insert(
+        header::HeaderName::from_static("preference-applied"),
+        HeaderValue::from_static(applied),
+    );
+    if let Some(location) = location.and_then(|location| HeaderValue::from_str(&location).ok()) {
+        res.headers_mut().insert(header::LOCATION, location);
+    }
+    res
+}
+
+/// The parameters that select a page, replaced when linking to another page.
+const PAGE_PARAMS: &[&str] = &["page", "items_per_page", "after", "before"];
+
+/// Returns the URL of the requested path and query, with its paging parameters replaced by `params`.
+fn list_url(req: &HttpRequest, params: &[(&str, String)]) -> String {
+    let mut query: Vec<String> = req
+        .query_string()
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .filter(|pair| !PAGE_PARAMS.contains(&pair.split('=').next().unwrap_or_default()))
+        .map(|pair| pair.to_owned())
+        .collect();
+    query.extend(params.iter().map(|(name, value)| format!("{}={}", name, value)));
+    format!("{}?{}", req.path(), query.join("&"))
+}
+
+/// Formats an RFC 8288 `Link` header from `(url, rel)` pairs.
+fn link_header(links: &[(&str, &str)]) -> Option<HeaderValue> {
+    let links: Vec<String> = links
+        .iter()
+        .map(|(url, rel)| format!("<{}>; rel=\"{}\"", url, rel))
+        .collect();
+    HeaderValue::from_str(&links.join(", ")).ok()
+}
+
+/// Links to the neighbouring pages of a `Page`, relative to the requested URL.
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct PageLinks {
+    #[serde(rename = "self")]
+    pub current: String,
+    pub first: String,
+    pub last: String,
+    pub next: Option<String>,
+    pub prev: Option<String>,
+}
+
+/// One page of a list endpoint. Pages are numbered from 0, like the `page` query parameter.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub page: usize,
+    pub per_page: usize,
+    pub total_items: usize,
+    pub total_pages: usize,
+    /// A cursor to continue after this page with keyset pagination, if there is a next page.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+    pub links: PageLinks,
+    /// The header row of the page written as CSV.
+    #[serde(skip)]
+    pub columns: Vec<String>,
+}
+
+impl<T> Page<T> {
+    /// Builds a page. Its links are filled in from the request when it is responded with.
+    pub fn new(items: Vec<T>, page: usize, per_page: usize, total_items: usize, columns: Vec<String>) -> Self {
+        Self {
+            items,
+            page,
+            per_page,
+            total_items,
+            total_pages: total_items.div_ceil(per_page.max(1)),
+            next_cursor: None,
+            links: PageLinks::default(),
+            columns,
+        }
+    }
+
+    fn page_url(&self, req: &HttpRequest, page: usize) -> String {
+        list_url(req, &[("page", page.to_string()), ("items_per_page", self.per_page.to_string())])
+    }
+
+    fn links(&self, req: &HttpRequest) -> PageLinks {
+        let last = self.total_pages.saturating_sub(1);
+        PageLinks {
+            current: self.page_url(req, self.page),
+            first: self.page_url(req, 0),
+            last: self.page_url(req, last),
+            next: (self.page < last).then(|| self.page_url(req, self.page + 1)),
+            prev: (self.page > 0).then(|| self.page_url(req, (self.page - 1).min(last))),
+        }
+    }
+}
+
+/// Responds with the page and its links, which are repeated in an RFC 8288 `Link` header.
+impl<T: Serialize> Responder for Page<T> {
+    type Body = BoxBody;
+    fn respond_to(mut self, req: &HttpRequest) -> HttpResponse {
+        self.links = self.links(req);
+        let mut links = vec![(self.links.first.as_str(), "first"), (self.links.last.as_str(), "last")];
+        if let Some(next) = &self.links.next {
+            links.push((next, "next"));
+        }
+        if let Some(prev) = &self.links.prev {
+            links.push((prev, "prev"));
+        }
+        let link = link_header(&links);
+
+        let mut res = negotiated_list_response(req, StatusCode::OK, &self, &self.columns, &self.items);
+        if let Some(link) = link {
+            res.headers_mut().insert(header::LINK, link);
+        }
+        res
+    }
+}
+
+/// Links to the neighbouring pages of a `CursorPage`, relative to the requested URL.
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct CursorLinks {
+    pub next: Option<String>,
+    pub prev: Option<String>,
+}
+
+/// One page of a list endpoint paginated by keyset. `next_cursor` continues after the last item with `?after=`,
+/// and `prev_cursor` goes back from the first item with `?before=`.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct CursorPage<T> {
+    pub items: Vec<T>,
+    pub per_page: usize,
+    pub next_cursor: Option<String>,
+    pub prev_cursor: Option<String>,
+    pub links: CursorLinks,
+    /// The header row of the page written as CSV.
+    #[serde(skip)]
+    pub columns: Vec<String>,
+}
+
+impl<T> CursorPage<T> {
+    pub fn new(
+        items: Vec<T>,
+        per_page: usize,
+        next_cursor: Option<String>,
+        prev_cursor: Option<String>,
+        columns: Vec<String>,
+    ) -> Self {
+        Self {
+            items,
+            per_page,
+            next_cursor,
+            prev_cursor,
+            links: CursorLinks::default(),
+            columns,
+        }
+    }
+}
+
+/// Responds with the page and its links, which are repeated in an RFC 8288 `Link` header.
+impl<T: Serialize> Responder for CursorPage<T> {
+    type Body = BoxBody;
+    fn respond_to(mut self, req: &HttpRequest) -> HttpResponse {
+        let per_page = self.per_page.to_string();
+        self.links = CursorLinks {
+            next: self
+                .next_cursor
+                .as_ref()
+                .map(|cursor| list_url(req, &[("after", cursor.clone()), ("items_per_page", per_page.clone())])),
+            prev: self
+                .prev_cursor
+                .as_ref()
+                .map(|cursor| list_url(req, &[("before", cursor.clone()), ("items_per_page", per_page.clone())])),
+        };
+        let mut links = vec![];
+        if let Some(next) = &self.links.next {
+            links.push((next.as_str(), "next"));
+        }
+        if let Some(prev) = &self.links.prev {
+            links.push((prev.as_str(), "prev"));
+        }
+        let link = link_header(&links).filter(|_| !links.is_empty());
+
+        let mut res = negotiated_list_response(req, StatusCode::OK, &self, &self.columns, &self.items);
+        if let Some(link) = link {
+            res.headers_mut().insert(header::LINK, link);
         }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    #[test]
+    fn test_return_preference() {
+        let req = TestRequest::default().to_http_request();
+        assert_eq!(ReturnPreference::from_request(&req), ReturnPreference::Representation);
+
+        let req = TestRequest::default()
+            .insert_header(("prefer", "respond-async, return=minimal"))
+            .to_http_request();
+        assert_eq!(ReturnPreference::from_request(&req), ReturnPreference::Minimal);
+    }
+
+    #[test]
+    fn test_page_links() {
+        let req = TestRequest::get()
+            .uri("/todos?query_string=pie&page=1&items_per_page=5")
+            .to_http_request();
+        let page = Page::new(vec![6, 7, 8, 9, 10], 1, 5, 12, vec![]);
+        assert_eq!(page.total_pages, 3);
+
+        let res = page.respond_to(&req);
+        assert_eq!(
+            res.headers().get(header::LINK).unwrap(),
+            "</todos?query_string=pie&page=0&items_per_page=5>; rel=\"first\", \
+             </todos?query_string=pie&page=2&items_per_page=5>; rel=\"last\", \
+             </todos?query_string=pie&page=2&items_per_page=5>; rel=\"next\", \
+             </todos?query_string=pie&page=0&items_per_page=5>; rel=\"prev\""
+        );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_negotiate_format() {
+        let all = [Format::Json, Format::Csv, Format::MessagePack];
+        let negotiate = |accept: Option<&str>, offered: &[Format]| {
+            let mut req = TestRequest::default();
+            if let Some(accept) = accept {
+                req = req.insert_header((header::ACCEPT, accept));
+            }
+            Format::negotiate(&req.to_http_request(), offered)
+        };
+
+        assert_eq!(negotiate(None, &all), Ok(Format::Json));
+        assert_eq!(negotiate(Some("*/*"), &all), Ok(Format::Json));
+        assert_eq!(negotiate(Some("text/csv"), &all), Ok(Format::Csv));
+        assert_eq!(negotiate(Some("text/*"), &all), Ok(Format::Csv));
+        assert_eq!(negotiate(Some("application/x-msgpack"), &all), Ok(Format::MessagePack));
+        assert_eq!(negotiate(Some("text/csv;q=0.5, application/msgpack"), &all), Ok(Format::MessagePack));
+        assert_eq!(negotiate(Some("text/csv;q=0, */*;q=0.1"), &all), Ok(Format::Json));
+        assert_eq!(negotiate(Some("application/json;q=0, */*"), &all), Ok(Format::Csv));
+        assert_eq!(
+            negotiate(Some("application/json;q=0, */*"), &[Format::Json, Format::MessagePack]),
+            Ok(Format::MessagePack)
+        );
+        assert!(matches!(
+            negotiate(Some("application/json;q=0, application/*;q=0"), &[Format::Json, Format::MessagePack]),
+            Err(CustomError::NotAcceptable { .. })
+        ));
+        assert!(matches!(
+            negotiate(Some("text/csv"), &[Format::Json, Format::MessagePack]),
+            Err(CustomError::NotAcceptable { .. })
+        ));
+        assert!(matches!(negotiate(Some("image/png"), &all), Err(CustomError::NotAcceptable { .. })));
+    }
+
+    #[test]
+    fn test_to_csv() {
+        let columns = ["id", "title", "done", "tags", "note"];
+        let rows = vec![
+            serde_json::json!({ "id": 1, "title": "Pay, rent", "done": false, "tags": ["home"] }),
+            serde_json::json!({ "id": 2, "title": "=HYPERLINK(\"x\")", "done": true, "note": null }),
+        ];
+        assert_eq!(
+            String::from_utf8(to_csv(&columns, &rows).unwrap()).unwrap(),
+            "id,title,done,tags,note\n\
+             1,\"Pay, rent\",false,\"[\"\"home\"\"]\",\n\
+             2,\"'=HYPERLINK(\"\"x\"\")\",true,,\n"
+        );
+        assert_eq!(
+            String::from_utf8(to_csv::<_, serde_json::Value>(&columns, &[]).unwrap()).unwrap(),
+            "id,title,done,tags,note\n"
+        );
+    }
+}
@@ -1,5 +1,5 @@
-use lib::core::config::{get_config, DatabaseSettings};
-use lib::core::telemetry::{get_subscriber, init_subscriber};
+use lib::core::config::{get_config, DatabaseSettings, Settings};
+use lib::core::telemetry::{get_subscriber, init_subscriber, LogLevelHandle};
 use migration::{Migrator, MigratorTrait};
 use once_cell::sync::Lazy;
 use sea_orm::{DbConn, DbErr};
@@ -21,15 +21,17 @@ struct TodoRes {
     id: usize,
 }
 
-static TRACING: Lazy<()> = Lazy::new(|| {
+static TRACING: Lazy<LogLevelHandle> = Lazy::new(|| {
     let default_filter_level = "info".to_string();
     let subscriber_name = "e2e".to_string();
     if std::env::var("TEST_LOG").is_ok() {
-        let subscriber = get_subscriber(subscriber_name, default_filter_level, std::io::stdout);
+        let (subscriber, log_level) = get_subscriber(subscriber_name, default_filter_level, std::io::stdout);
         init_subscriber(subscriber);
+        log_level
     } else {
-        let subscriber = get_subscriber(subscriber_name, default_filter_level, std::io::sink);
+        let (subscriber, log_level) = get_subscriber(subscriber_name, default_filter_level, std::io::sink);
         init_subscriber(subscriber);
+        log_level
     }
 });
 
@@ -72,6 +74,43 @@ async fn add_todo_returns_201() {
     }
 }
 
+#[tokio::test]
+async fn add_todo_returns_the_created_todo_and_its_location() {
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+
+    let response = client
+        .post(format!("{}/todos", test_app.address))
+        .header("Content-Type", "application/json")
+        .body(r#"{"title": "test1", "description": "test1", "done": false}"#)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(201, response.status().as_u16());
+    let location = response.headers()["location"].to_str().unwrap().to_owned();
+    let body: serde_json::Value = response.json().await.expect("Response is not JSON");
+    assert_eq!(format!("/todos/{}", body["id"]), location);
+    assert_eq!("test1", body["title"]);
+    assert!(body["created_at"].is_string());
+    assert!(body["updated_at"].is_string());
+
+    let response = client
+        .post(format!("{}/todos", test_app.address))
+        .header("Content-Type", "application/json")
+        .header("Prefer", "return=minimal")
+        .body(r#"{"title": "test2", "description": "test2", "done": false}"#)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(201, response.status().as_u16());
+    assert_eq!("return=minimal", response.headers()["preference-applied"]);
+    let body: serde_json::Value = response.json().await.expect("Response is not JSON");
+    assert_eq!("Created", body["message"]);
+    assert!(body.get("title").is_none());
+}
+
 #[tokio::test]
 async fn add_todo_returns_400_for_invalid_data() {
     let test_app = spawn_app().await;
@@ -114,6 +153,7 @@ async fn add_todo_returns_409_if_title_already_exists() {
         .expect("Failed to execute request.");
 
     assert_eq!(201, response.status().as_u16());
+    let created: serde_json::Value = response.json().await.expect("Response is not JSON");
 
     let response = client
         .post(format!("{}/todos", test_app.address))
@@ -124,6 +164,44 @@ async fn add_todo_returns_409_if_title_already_exists() {
         .expect("Failed to execute request.");
 
     assert_eq!(409, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.expect("Response is not JSON");
+    assert_eq!("TODO_TITLE_TAKEN", body["code"]);
+    assert_eq!("title", body["conflict"]["field"]);
+    assert_eq!("todo_title_key", body["conflict"]["constraint"]);
+    assert_eq!(created["id"], body["conflict"]["existing_id"]);
+    assert_eq!(format!("/todos/{}", created["id"]), body["conflict"]["existing_url"]);
+}
+
+#[tokio::test]
+async fn update_todo_returns_409_if_title_already_exists() {
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+
+    let mut ids = vec![];
+    for title in ["test1", "test2"] {
+        let response = client
+            .post(format!("{}/todos", test_app.address))
+            .header("Content-Type", "application/json")
+            .body(format!(r#"{{"title": "{}", "description": "test", "done": false}}"#, title))
+            .send()
+            .await
+            .expect("Failed to execute request.");
+        let created: serde_json::Value = response.json().await.expect("Response is not JSON");
+        ids.push(created["id"].clone());
+    }
+
+    let response = client
+        .put(format!("{}/todos/{}", test_app.address, ids[1]))
+        .header("Content-Type", "application/json")
+        .body(r#"{"title": "test1"}"#)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(409, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.expect("Response is not JSON");
+    assert_eq!("TODO_TITLE_TAKEN", body["code"]);
+    assert_eq!(ids[0], body["conflict"]["existing_id"]);
 }
 
 #[tokio::test]
@@ -140,6 +218,400 @@ async fn get_todos_returns_200() {
     assert_eq!(200, response.status().as_u16());
 }
 
+#[tokio::test]
+async fn get_todos_returns_a_page_with_links() {
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+
+    for title in ["test1", "test2", "test3"] {
+        client
+            .post(format!("{}/todos", test_app.address))
+            .header("Content-Type", "application/json")
+            .body(format!(r#"{{"title": "{}", "description": "test", "done": false}}"#, title))
+            .send()
+            .await
+            .expect("Failed to execute request.");
+    }
+
+    let response = client
+        .get(format!("{}/todos?items_per_page=2", test_app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(200, response.status().as_u16());
+    let link = response.headers()["link"].to_str().unwrap().to_owned();
+    assert!(link.contains(r#"</todos?page=1&items_per_page=2>; rel="next""#));
+    let body: serde_json::Value = response.json().await.expect("Response is not JSON");
+    assert_eq!(2, body["items"].as_array().unwrap().len());
+    assert_eq!(0, body["page"]);
+    assert_eq!(2, body["per_page"]);
+    assert_eq!(3, body["total_items"]);
+    assert_eq!(2, body["total_pages"]);
+    assert_eq!("/todos?page=1&items_per_page=2", body["links"]["next"]);
+    assert!(body["links"]["prev"].is_null());
+}
+
+#[tokio::test]
+async fn get_todos_follows_cursors_forward_and_back() {
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+
+    for title in ["test1", "test2", "test3"] {
+        client
+            .post(format!("{}/todos", test_app.address))
+            .header("Content-Type", "application/json")
+            .body(format!(r#"{{"title": "{}", "description": "test", "done": false}}"#, title))
+            .send()
+            .await
+            .expect("Failed to execute request.");
+    }
+
+    let titles = |body: &serde_json::Value| -> Vec<String> {
+        body["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|todo| todo["title"].as_str().unwrap().to_owned())
+            .collect()
+    };
+
+    let first: serde_json::Value = client
+        .get(format!("{}/todos?items_per_page=2", test_app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json()
+        .await
+        .expect("Response is not JSON");
+    let after = first["next_cursor"].as_str().expect("No next cursor").to_owned();
+
+    let response = client
+        .get(format!("{}/todos?items_per_page=2&after={}", test_app.address, after))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(200, response.status().as_u16());
+    assert!(response.headers()["link"].to_str().unwrap().contains(r#"rel="prev""#));
+    let second: serde_json::Value = response.json().await.expect("Response is not JSON");
+    assert_eq!(vec!["test1"], titles(&second));
+    assert!(second["next_cursor"].is_null());
+
+    let before = second["prev_cursor"].as_str().expect("No prev cursor").to_owned();
+    let back: serde_json::Value = client
+        .get(format!("{}/todos?items_per_page=2&before={}", test_app.address, before))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json()
+        .await
+        .expect("Response is not JSON");
+    assert_eq!(titles(&first), titles(&back));
+
+    let response = client
+        .get(format!("{}/todos?after=garbage", test_app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(400, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn get_todos_filters_and_sorts() {
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+
+    for (title, done) in [("b", true), ("c", false), ("a", true)] {
+        client
+            .post(format!("{}/todos", test_app.address))
+            .header("Content-Type", "application/json")
+            .body(format!(r#"{{"title": "{}", "description": "test", "done": {}}}"#, title, done))
+            .send()
+            .await
+            .expect("Failed to execute request.");
+    }
+
+    let body: serde_json::Value = client
+        .get(format!("{}/todos?done=true&sort=title", test_app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json()
+        .await
+        .expect("Response is not JSON");
+    let titles: Vec<&str> = body["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|todo| todo["title"].as_str().unwrap())
+        .collect();
+    assert_eq!(vec!["a", "b"], titles);
+
+    let response = client
+        .get(format!("{}/todos?sort=-password", test_app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(400, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn get_todos_accepts_a_filter_expression() {
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+
+    for (title, done) in [("pay rent", false), ("pay bills", true), ("walk the dog", false)] {
+        client
+            .post(format!("{}/todos", test_app.address))
+            .header("Content-Type", "application/json")
+            .body(format!(r#"{{"title": "{}", "description": "test", "done": {}}}"#, title, done))
+            .send()
+            .await
+            .expect("Failed to execute request.");
+    }
+
+    let body: serde_json::Value = client
+        .get(format!("{}/todos", test_app.address))
+        .query(&[("filter", "done eq false and (title startswith 'pay' or title contains 'dog')"), ("sort", "title")])
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json()
+        .await
+        .expect("Response is not JSON");
+    let titles: Vec<&str> = body["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|todo| todo["title"].as_str().unwrap())
+        .collect();
+    assert_eq!(vec!["pay rent", "walk the dog"], titles);
+
+    let response = client
+        .get(format!("{}/todos", test_app.address))
+        .query(&[("filter", "done eq 'no'")])
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(400, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.expect("Response is not JSON");
+    assert_eq!(
+        "Invalid filter: 'no' is not a valid value for boolean field `done` at position 9",
+        body["message"]
+    );
+}
+
+#[tokio::test]
+async fn get_todos_searches_title_and_description() {
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+
+    for (title, description) in [("Groceries", "Buy milk <b>and</b> eggs"), ("Milk run", "Corner shop"), ("Taxes", "File them")] {
+        client
+            .post(format!("{}/todos", test_app.address))
+            .header("Content-Type", "application/json")
+            .body(format!(r#"{{"title": "{}", "description": "{}", "done": false}}"#, title, description))
+            .send()
+            .await
+            .expect("Failed to execute request.");
+    }
+
+    let body: serde_json::Value = client
+        .get(format!("{}/todos?q=milk", test_app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json()
+        .await
+        .expect("Response is not JSON");
+
+    let items = body["items"].as_array().unwrap();
+    assert_eq!(2, items.len());
+    // Title matches rank above description matches
+    assert_eq!("Milk run", items[0]["title"]);
+    assert_eq!("<mark>Milk</mark> run", items[0]["highlights"]["title"]);
+    assert_eq!("Groceries", items[1]["title"]);
+    let description = items[1]["highlights"]["description"].as_str().unwrap();
+    assert!(description.contains("<mark>milk</mark>"));
+    // Only the highlights are markup, the text itself is escaped
+    assert!(description.contains("&lt;b&gt;and&lt;/b&gt;"));
+    assert!(items[0]["rank"].as_f64().unwrap() > items[1]["rank"].as_f64().unwrap());
+}
+
+#[tokio::test]
+async fn suggest_todos_tolerates_typos() {
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+
+    for title in ["Buy groceries", "Pay rent"] {
+        client
+            .post(format!("{}/todos", test_app.address))
+            .header("Content-Type", "application/json")
+            .body(format!(r#"{{"title": "{}", "description": "test", "done": false}}"#, title))
+            .send()
+            .await
+            .expect("Failed to execute request.");
+    }
+
+    for prefix in ["buy gro", "buy grocerys"] {
+        let response = client
+            .get(format!("{}/todos/suggest", test_app.address))
+            .query(&[("prefix", prefix)])
+            .send()
+            .await
+            .expect("Failed to execute request.");
+        assert_eq!(200, response.status().as_u16());
+        let body: serde_json::Value = response.json().await.expect("Response is not JSON");
+        assert_eq!("Buy groceries", body["items"][0]["title"], "{}", prefix);
+        assert_eq!(1, body["items"].as_array().unwrap().len());
+    }
+
+    let response = client
+        .get(format!("{}/todos/suggest?prefix=", test_app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(400, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn get_todos_returns_only_the_requested_fields() {
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+
+    let created: serde_json::Value = client
+        .post(format!("{}/todos", test_app.address))
+        .header("Content-Type", "application/json")
+        .body(r#"{"title": "test", "description": "a long description", "done": false}"#)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json()
+        .await
+        .expect("Response is not JSON");
+
+    let body: serde_json::Value = client
+        .get(format!("{}/todos?fields=id,title,done", test_app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json()
+        .await
+        .expect("Response is not JSON");
+    assert_eq!(
+        serde_json::json!([{ "id": created["id"], "title": "test", "done": false }]),
+        body["items"]
+    );
+
+    let body: serde_json::Value = client
+        .get(format!("{}/todos/{}?fields=title", test_app.address, created["id"]))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json()
+        .await
+        .expect("Response is not JSON");
+    assert_eq!(serde_json::json!({ "title": "test" }), body);
+
+    let response = client
+        .get(format!("{}/todos?fields=id,secret", test_app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(400, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn todos_can_be_written_as_csv_and_read_as_csv_or_msgpack() {
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+
+    let response = client
+        .post(format!("{}/todos", test_app.address))
+        .header("Content-Type", "text/csv")
+        .body("title,description,done\nPay rent,\"Before the 5th, or else\",false\n")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(201, response.status().as_u16());
+
+    let response = client
+        .get(format!("{}/todos?fields=title,description,done", test_app.address))
+        .header("Accept", "text/csv")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(200, response.status().as_u16());
+    assert_eq!("text/csv; charset=utf-8", response.headers()["content-type"]);
+    assert_eq!(
+        "title,description,done\nPay rent,\"Before the 5th, or else\",false\n",
+        response.text().await.unwrap()
+    );
+
+    // An empty page still has the header row
+    let response = client
+        .get(format!("{}/todos?fields=title,done&done=true", test_app.address))
+        .header("Accept", "text/csv")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!("title,done\n", response.text().await.unwrap());
+
+    let response = client
+        .get(format!("{}/todos?fields=title", test_app.address))
+        .header("Accept", "application/msgpack")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!("application/msgpack", response.headers()["content-type"]);
+    let body: serde_json::Value = rmp_serde::from_slice(&response.bytes().await.unwrap()).unwrap();
+    assert_eq!("Pay rent", body["items"][0]["title"]);
+
+    let response = client
+        .get(format!("{}/error_codes", test_app.address))
+        .header("Accept", "application/json;q=0, */*")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!("text/csv; charset=utf-8", response.headers()["content-type"]);
+
+    let response = client
+        .get(format!("{}/error_codes", test_app.address))
+        .header("Accept", "image/png")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(406, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn get_todos_returns_400_if_page_size_exceeds_maximum() {
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+
+    let response = client
+        .get(format!("{}/todos?items_per_page=100000000", test_app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(400, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn get_todos_returns_400_if_page_is_out_of_range() {
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+
+    let response = client
+        .get(format!("{}/todos?page={}", test_app.address, usize::MAX))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(400, response.status().as_u16());
+}
+
 #[tokio::test]
 async fn get_todo_by_id_returns_200_if_exists() {
     let test_app = spawn_app().await;
@@ -248,6 +720,166 @@ async fn update_todo_by_id_returns_404_if_not_exists() {
     assert_eq!(404, response.status().as_u16());
 }
 
+#[tokio::test]
+async fn get_todo_by_id_returns_304_if_etag_matches() {
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+
+    let todo_req = TodoReq {
+        title: "test".to_string(),
+        description: "test".to_string(),
+        done: false,
+    };
+
+    let response = client
+        .post(format!("{}/todos", test_app.address))
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_string(&todo_req).unwrap())
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    let todo_res: TodoRes = response.json().await.unwrap();
+
+    let response = client
+        .get(format!("{}/todos/{}", test_app.address, todo_res.id))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(200, response.status().as_u16());
+    let etag = response.headers()["ETag"].to_str().unwrap().to_owned();
+    assert!(etag.starts_with('"'));
+
+    let response = client
+        .get(format!("{}/todos/{}", test_app.address, todo_res.id))
+        .header("If-None-Match", &etag)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(304, response.status().as_u16());
+    assert_eq!(response.headers()["ETag"], etag.as_str());
+
+    // The MessagePack representation has a tag of its own
+    let response = client
+        .get(format!("{}/todos/{}", test_app.address, todo_res.id))
+        .header("Accept", "application/msgpack")
+        .header("If-None-Match", &etag)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(200, response.status().as_u16());
+    assert_ne!(response.headers()["ETag"], etag.as_str());
+}
+
+#[tokio::test]
+async fn writes_return_412_if_etag_is_stale() {
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+
+    let todo_req = TodoReq {
+        title: "test".to_string(),
+        description: "test".to_string(),
+        done: false,
+    };
+
+    let response = client
+        .post(format!("{}/todos", test_app.address))
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_string(&todo_req).unwrap())
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    let stale = response.headers()["ETag"].to_str().unwrap().to_owned();
+    let todo_res: TodoRes = response.json().await.unwrap();
+    let url = format!("{}/todos/{}", test_app.address, todo_res.id);
+
+    let response = client
+        .put(&url)
+        .header("If-Match", &stale)
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_string(&serde_json::json!({ "done": true })).unwrap())
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(200, response.status().as_u16());
+    let current = response.headers()["ETag"].to_str().unwrap().to_owned();
+    assert_ne!(current, stale);
+
+    // a second client still holding the old ETag does not overwrite the update
+    let response = client
+        .put(&url)
+        .header("If-Match", &stale)
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_string(&serde_json::json!({ "done": false })).unwrap())
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(412, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "PRECONDITION_FAILED");
+
+    let response = client
+        .delete(&url)
+        .header("If-Match", &stale)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(412, response.status().as_u16());
+
+    let response = client
+        .delete(&url)
+        .header("If-Match", &current)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(200, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn writes_return_428_without_if_match_if_required() {
+    let test_app = spawn_app_with(|config| config.require_if_match = true).await;
+    let client = reqwest::Client::new();
+
+    let todo_req = TodoReq {
+        title: "test".to_string(),
+        description: "test".to_string(),
+        done: false,
+    };
+
+    let response = client
+        .post(format!("{}/todos", test_app.address))
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_string(&todo_req).unwrap())
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(201, response.status().as_u16());
+    let etag = response.headers()["ETag"].to_str().unwrap().to_owned();
+    let todo_res: TodoRes = response.json().await.unwrap();
+    let url = format!("{}/todos/{}", test_app.address, todo_res.id);
+
+    let response = client
+        .put(&url)
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_string(&serde_json::json!({ "done": true })).unwrap())
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(428, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "PRECONDITION_REQUIRED");
+
+    let response = client.delete(&url).send().await.expect("Failed to execute request.");
+    assert_eq!(428, response.status().as_u16());
+
+    let response = client
+        .delete(&url)
+        .header("If-Match", &etag)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(200, response.status().as_u16());
+}
+
 #[tokio::test]
 async fn delete_todo_by_id_returns_200_if_exists() {
     let test_app = spawn_app().await;
@@ -398,23 +1030,105 @@ async fn bulk_delete_todos_returns_404_if_some_does_not_exist() {
     assert_eq!(404, response.status().as_u16());
 }
 
+#[tokio::test]
+async fn extractor_errors_return_json_400() {
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+
+    let responses = vec![
+        client
+            .post(format!("{}/todos", test_app.address))
+            .header("Content-Type", "application/json")
+            .body(r#"{"title": "#)
+            .send()
+            .await
+            .expect("Failed to execute request."),
+        client
+            .get(format!("{}/todos/abc", test_app.address))
+            .send()
+            .await
+            .expect("Failed to execute request."),
+        client
+            .get(format!("{}/todos?page=x", test_app.address))
+            .send()
+            .await
+            .expect("Failed to execute request."),
+    ];
+
+    for response in responses {
+        assert_eq!(400, response.status().as_u16());
+        let body: serde_json::Value = response.json().await.expect("Response is not JSON");
+        assert_eq!(400, body["status_code"]);
+    }
+}
+
+#[tokio::test]
+async fn unknown_route_returns_json_404() {
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+
+    let response = client
+        .get(format!("{}/does_not_exist", test_app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(404, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.expect("Response is not JSON");
+    assert_eq!(404, body["status_code"]);
+}
+
+#[tokio::test]
+async fn wrong_method_returns_405_with_allow_header() {
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+
+    let response = client
+        .patch(format!("{}/todos/1", test_app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(405, response.status().as_u16());
+    assert_eq!("GET, PUT, DELETE", response.headers()["allow"]);
+}
+
+#[tokio::test]
+async fn get_log_level_returns_401_without_token() {
+    let test_app = spawn_app().await;
+    let client = reqwest::Client::new();
+
+    let response = client
+        .get(format!("{}/admin/log_level", test_app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(401, response.status().as_u16());
+}
+
 pub struct TestApp {
     pub address: String,
     pub db: DbConn,
 }
 
 async fn spawn_app() -> TestApp {
-    Lazy::force(&TRACING);
+    spawn_app_with(|_| {}).await
+}
+
+async fn spawn_app_with(configure: impl FnOnce(&mut Settings)) -> TestApp {
+    let log_level = Lazy::force(&TRACING).clone();
     let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind random port");
     let port = listener.local_addr().unwrap().port();
     let address = format!("http://127.0.0.1:{}", port);
 
     let mut configuration = get_config().expect("Failed to read configuration.");
     configuration.database.database_name = Uuid::new_v4().to_string();
+    configure(&mut configuration);
     let db = configure_database(&configuration.database).await;
     let db_clone = get_database_conn(&configuration.database).await;
 
-    let server = lib::core::startup::run(listener, db).expect("Failed to bind address");
+    let server = lib::core::startup::run(listener, db, configuration, log_level).expect("Failed to bind address");
     let _ = tokio::spawn(server);
     TestApp {
         address,